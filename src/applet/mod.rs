@@ -1,5 +1,6 @@
 use cosmic::app::{Core, Task};
 use cosmic::iced::alignment::{Horizontal, Vertical};
+use cosmic::iced::futures::SinkExt;
 use cosmic::iced::{window, Alignment, Length, Subscription};
 use cosmic::surface::action;
 use cosmic::iced_runtime::core::window::Id as SurfaceId;
@@ -7,9 +8,10 @@ use cosmic::widget::{self, button};
 use cosmic::{Element, Theme};
 
 use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 use crate::config::Config;
-use crate::timer::{BreakType, TimerService, TimerState};
+use crate::timer::{IdleStatus, TimerEvent, TimerService, TimerSnapshot, TimerState};
 use crate::break_screen;
 
 /// Messages that the applet can handle
@@ -17,20 +19,22 @@ use crate::break_screen;
 pub enum Message {
     /// Toggle the popup window
     TogglePopup,
-    /// Update timer tick
+    /// Refresh the display while the popup is open
     Tick,
     /// Timer state updated
-    TimerUpdate {
-        short_remaining: chrono::Duration,
-        long_remaining: chrono::Duration,
-        state: TimerState,
-    },
-    /// Start a break immediately
-    StartBreak(BreakType),
+    TimerUpdate(TimerSnapshot),
+    /// Event published by the timer service
+    TimerEvent(TimerEvent),
+    /// Start a break of the named kind immediately
+    StartBreak(String),
     /// Skip current break
     SkipBreak,
-    /// Postpone break
-    PostponeBreak(BreakType),
+    /// Postpone the named break kind
+    PostponeBreak(String),
+    /// Hold back breaks for a focus block of the configured length
+    StartFocus,
+    /// End the focus block early
+    EndFocus,
     /// Configuration changed
     ConfigChanged(Config),
     /// Popup closed
@@ -39,7 +43,7 @@ pub enum Message {
     BreakScreenClosed(SurfaceId),
     /// Break screen action
     BreakScreenAction(break_screen::Message),
-    /// Break countdown tick
+    /// Break screen countdown tick
     BreakTick,
     /// Surface action (for popups)
     Surface(cosmic::surface::Action),
@@ -53,16 +57,14 @@ pub struct CosmicEyes {
     popup: Option<SurfaceId>,
     icon_name: String,
     // Timer display state
-    next_short_break: Option<chrono::Duration>,
-    next_long_break: Option<chrono::Duration>,
+    snapshot: Option<TimerSnapshot>,
     timer_state: TimerState,
     // Break screen state
     break_window: Option<SurfaceId>,
     break_screen: Option<break_screen::BreakScreen>,
-    break_remaining: u64,
-    // Notification tracking
-    short_break_notified: bool,
-    long_break_notified: bool,
+    // A break started while the session was locked; its window opens once
+    // the session is back
+    break_deferred: bool,
 }
 
 impl CosmicEyes {
@@ -75,17 +77,34 @@ impl CosmicEyes {
             config,
             popup: None,
             icon_name: "cosmic-eyes-symbolic".to_string(),
-            next_short_break: None,
-            next_long_break: None,
+            snapshot: None,
             timer_state: TimerState::Running,
             break_window: None,
             break_screen: None,
-            break_remaining: 0,
-            short_break_notified: false,
-            long_break_notified: false,
+            break_deferred: false,
         }
     }
 
+    /// Forward timer events into the update loop
+    fn timer_events(mut events: broadcast::Receiver<TimerEvent>) -> Subscription<Message> {
+        Subscription::run_with_id(
+            std::any::TypeId::of::<TimerEvent>(),
+            cosmic::iced::stream::channel(16, move |mut output| async move {
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            if output.send(Message::TimerEvent(event)).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            }),
+        )
+    }
+
     /// Format time duration for display
     fn format_duration(duration: chrono::Duration) -> String {
         let total_seconds = duration.num_seconds().max(0);
@@ -117,15 +136,86 @@ impl cosmic::Application for CosmicEyes {
     fn init(core: Core, config: Self::Flags) -> (Self, Task<Self::Message>) {
         let app = Self::new(config);
 
-        // Start D-Bus service in background
+        // The timer engine wakes up on its own deadlines, so nothing needs
+        // to poll it
+        tokio::spawn(app.timer_service.clone().run());
+
+        // One connection per bus serves the applet for its lifetime. The
+        // session bus carries the D-Bus interface, notifications and idle
+        // detection, the system bus talks to logind
         let timer = app.timer_service.clone();
+        let events = app.timer_service.subscribe();
         tokio::spawn(async move {
-            if let Err(e) = crate::dbus::start_service(timer).await {
-                eprintln!("Failed to start D-Bus service: {}", e);
+            let session = match zbus::Connection::session().await {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    eprintln!("Failed to connect to the session bus: {}", e);
+                    None
+                }
+            };
+            let system = match zbus::Connection::system().await {
+                Ok(system) => Some(system),
+                Err(e) => {
+                    eprintln!("Failed to connect to the system bus: {}", e);
+                    None
+                }
+            };
+
+            if let Some(session) = &session {
+                if let Err(e) = crate::dbus::start_service(timer.clone(), session).await {
+                    eprintln!("Failed to start D-Bus service: {}", e);
+                }
+
+                // Pre-break warnings and questions on return become desktop
+                // notifications
+                match crate::notify::Notifier::new(session).await {
+                    Ok(notifier) => {
+                        tokio::spawn(crate::notify::watch_events(
+                            events,
+                            notifier,
+                            timer.clone(),
+                        ));
+                    }
+                    Err(e) => eprintln!("Failed to set up notifications: {}", e),
+                }
             }
+
+            if let Some(system) = &system {
+                // Track suspend/resume so sleeping over a break counts as
+                // taking it
+                let (sleep_timer, sleep_system) = (timer.clone(), system.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = crate::logind::watch_sleep(sleep_timer, sleep_system).await {
+                        eprintln!("Failed to watch logind sleep signals: {}", e);
+                    }
+                });
+
+                // Pause while the session is locked or switched away from
+                let (session_timer, session_system) = (timer.clone(), system.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = crate::logind::watch_session(session_timer, session_system).await {
+                        eprintln!("Failed to watch the logind session: {}", e);
+                    }
+                });
+            }
+
+            // Pause while the user is away
+            crate::idle::watch(timer, session, system).await;
         });
 
-        (app, Task::none())
+        // Keep a log of finished breaks
+        tokio::spawn(crate::history::watch_events(
+            app.timer_service.subscribe(),
+            app.timer_service.clock(),
+        ));
+
+        let timer = app.timer_service.clone();
+        let task = Task::perform(
+            async move { timer.snapshot().await },
+            |snapshot| cosmic::Action::App(Message::TimerUpdate(snapshot)),
+        );
+
+        (app, task)
     }
 
     fn update(&mut self, message: Self::Message) -> Task<Self::Message> {
@@ -162,101 +252,74 @@ impl cosmic::Application for CosmicEyes {
                     cosmic::app::Action::Surface(action),
                 ));
             }
-            Message::Tick => {
+            // The break countdown comes from the timer as well, since input
+            // during a break may hold it up
+            Message::Tick | Message::BreakTick | Message::TimerEvent(_) => {
                 // Query timer service and update display
                 let timer = self.timer_service.clone();
-                let config = self.config.clone();
                 Task::perform(
-                    async move {
-                        // Check for idle detection
-                        if config.idle_detection {
-                            let is_idle = crate::idle::is_idle(config.idle_threshold).await;
-                            let state = timer.state().await;
-                            let is_paused = matches!(state, TimerState::Paused);
-
-                            // Auto-pause when idle, auto-resume when active
-                            if is_idle && !is_paused {
-                                timer.pause().await;
-                            } else if !is_idle && is_paused {
-                                timer.resume().await;
-                            }
-                        }
-
-                        let short_remaining = timer.time_until_short_break().await;
-                        let long_remaining = timer.time_until_long_break().await;
-                        let state = timer.state().await;
-
-                        // Check if it's time for a break
-                        if let Some(break_type) = timer.check_break_time().await {
-                            timer.start_break(break_type).await;
-                        }
-
-                        Message::TimerUpdate {
-                            short_remaining,
-                            long_remaining,
-                            state,
-                        }
-                    },
-                    |msg| cosmic::Action::App(msg),
+                    async move { timer.snapshot().await },
+                    |snapshot| cosmic::Action::App(Message::TimerUpdate(snapshot)),
                 )
             }
-            Message::TimerUpdate { short_remaining, long_remaining, state } => {
+            Message::TimerUpdate(snapshot) => {
                 // Update display state
-                self.next_short_break = Some(short_remaining);
-                self.next_long_break = Some(long_remaining);
-
-                // Send pre-break notifications
-                let notify_threshold = self.config.notification_before_break as i64;
-
-                // Check short break notification
-                if short_remaining.num_seconds() <= notify_threshold && !self.short_break_notified {
-                    self.short_break_notified = true;
-                    tokio::spawn(async move {
-                        crate::notify::send_notification(
-                            "Short Break Soon",
-                            "Your short break will start soon. Save your work!"
-                        ).await;
-                    });
-                } else if short_remaining.num_seconds() > notify_threshold + 10 {
-                    // Reset notification flag when timer is well above threshold
-                    self.short_break_notified = false;
+                let state = snapshot.state.clone();
+                let pomodoro = snapshot.pomodoro;
+                let break_remaining = snapshot
+                    .break_remaining
+                    .map(|remaining| remaining.num_seconds().max(0) as u64);
+                let break_interrupted = snapshot.break_interrupted;
+                let postpones_left = match &state {
+                    TimerState::InBreak(name) => snapshot
+                        .postpones_left
+                        .iter()
+                        .find(|(kind, _)| kind == name)
+                        .map(|(_, left)| *left),
+                    _ => None,
+                };
+                let session_away = snapshot.session_away;
+                self.snapshot = Some(snapshot);
+
+                // Check if we're entering a break state, or one that started
+                // while the session was locked
+                let in_break = matches!(state, TimerState::InBreak(_));
+                let mut entering_break = in_break
+                    && (self.break_deferred || !matches!(self.timer_state, TimerState::InBreak(_)));
+                self.break_deferred = false;
+                // Never show the break window over the lock screen
+                if entering_break && session_away {
+                    self.break_deferred = true;
+                    entering_break = false;
                 }
 
-                // Check long break notification
-                if long_remaining.num_seconds() <= notify_threshold && !self.long_break_notified {
-                    self.long_break_notified = true;
-                    tokio::spawn(async move {
-                        crate::notify::send_notification(
-                            "Long Break Soon",
-                            "Your long break will start soon. Finish up what you're doing!"
-                        ).await;
-                    });
-                } else if long_remaining.num_seconds() > notify_threshold + 10 {
-                    // Reset notification flag when timer is well above threshold
-                    self.long_break_notified = false;
-                }
-
-                // Check if we're entering a break state
-                let entering_break = !matches!(self.timer_state, TimerState::InBreak(_))
-                    && matches!(state, TimerState::InBreak(_));
-
                 self.timer_state = state.clone();
 
                 // Create break screen window if entering break
                 if entering_break {
-                    if let TimerState::InBreak(break_type) = state {
-                        let duration_seconds = match break_type {
-                            BreakType::Short => self.config.short_break.duration,
-                            BreakType::Long => self.config.long_break.duration,
+                    if let TimerState::InBreak(name) = state {
+                        // Settings in effect right now, with overrides
+                        let config = self.config.at(chrono::Local::now());
+                        let Some(break_config) = config.break_config(&name) else {
+                            return Task::none();
                         };
+                        let duration_seconds = break_remaining.unwrap_or(break_config.duration);
+                        // Breaks that used up their postpones are enforced
+                        let strict = config.is_strict(&name) || postpones_left == Some(0);
+
+                        let progress = pomodoro.map(|(pomodoro, cycles)| {
+                            format!("Pomodoro {} of {} complete", pomodoro, cycles)
+                        });
 
                         self.break_screen = Some(break_screen::BreakScreen::new(
-                            break_type,
+                            break_config.title(),
+                            break_config.message.clone(),
+                            progress,
                             duration_seconds,
-                            self.config.allow_skip && !self.config.strict_mode,
-                            self.config.allow_postpone && !self.config.strict_mode,
+                            config.allow_skip && !strict,
+                            config.allow_postpone && !strict,
+                            postpones_left,
                         ));
-                        self.break_remaining = duration_seconds;
 
                         let window_settings = window::Settings {
                             size: cosmic::iced::Size::new(800.0, 600.0),
@@ -273,6 +336,13 @@ impl cosmic::Application for CosmicEyes {
                     }
                 }
 
+                if let Some(ref mut screen) = self.break_screen {
+                    if let Some(remaining) = break_remaining {
+                        screen.update_remaining(remaining);
+                    }
+                    screen.set_interrupted(break_interrupted);
+                }
+
                 // Close break screen window if exiting break
                 if let Some(window_id) = self.break_window {
                     if !matches!(self.timer_state, TimerState::InBreak(_)) {
@@ -284,12 +354,30 @@ impl cosmic::Application for CosmicEyes {
 
                 Task::none()
             }
-            Message::StartBreak(break_type) => {
+            Message::StartBreak(name) => {
                 // Start break immediately
                 let timer = self.timer_service.clone();
                 Task::perform(
                     async move {
-                        timer.start_break(break_type).await;
+                        let _ = timer.start_break(&name).await;
+                    },
+                    |_| cosmic::Action::App(Message::Tick),
+                )
+            }
+            Message::StartFocus => {
+                let timer = self.timer_service.clone();
+                Task::perform(
+                    async move {
+                        let _ = timer.start_focus(None).await;
+                    },
+                    |_| cosmic::Action::App(Message::Tick),
+                )
+            }
+            Message::EndFocus => {
+                let timer = self.timer_service.clone();
+                Task::perform(
+                    async move {
+                        let _ = timer.end_focus().await;
                     },
                     |_| cosmic::Action::App(Message::Tick),
                 )
@@ -298,16 +386,16 @@ impl cosmic::Application for CosmicEyes {
                 let timer = self.timer_service.clone();
                 Task::perform(
                     async move {
-                        timer.skip_break().await;
+                        let _ = timer.skip_break().await;
                     },
                     |_| cosmic::Action::App(Message::Tick),
                 )
             }
-            Message::PostponeBreak(break_type) => {
+            Message::PostponeBreak(name) => {
                 let timer = self.timer_service.clone();
                 Task::perform(
                     async move {
-                        timer.postpone_break(break_type).await;
+                        let _ = timer.postpone_break(&name).await;
                     },
                     |_| cosmic::Action::App(Message::Tick),
                 )
@@ -336,7 +424,7 @@ impl cosmic::Application for CosmicEyes {
                     let timer = self.timer_service.clone();
                     return Task::perform(
                         async move {
-                            timer.end_break().await;
+                            let _ = timer.end_break().await;
                         },
                         |_| cosmic::Action::App(Message::Tick),
                     );
@@ -355,7 +443,7 @@ impl cosmic::Application for CosmicEyes {
                                 window::close(window_id),
                                 Task::perform(
                                     async move {
-                                        timer.skip_break().await;
+                                        let _ = timer.skip_break().await;
                                     },
                                     |_| cosmic::Action::App(Message::Tick),
                                 ),
@@ -365,8 +453,8 @@ impl cosmic::Application for CosmicEyes {
                     break_screen::Message::Postpone => {
                         // Close break window and postpone
                         if let Some(window_id) = self.break_window {
-                            if let TimerState::InBreak(break_type) = &self.timer_state {
-                                let break_type = *break_type;
+                            if let TimerState::InBreak(name) = &self.timer_state {
+                                let name = name.clone();
                                 self.break_window = None;
                                 self.break_screen = None;
                                 let timer = self.timer_service.clone();
@@ -374,7 +462,7 @@ impl cosmic::Application for CosmicEyes {
                                     window::close(window_id),
                                     Task::perform(
                                         async move {
-                                            timer.postpone_break(break_type).await;
+                                            let _ = timer.postpone_break(&name).await;
                                         },
                                         |_| cosmic::Action::App(Message::Tick),
                                     ),
@@ -396,34 +484,6 @@ impl cosmic::Application for CosmicEyes {
                 }
                 Task::none()
             }
-            Message::BreakTick => {
-                // Update break countdown
-                if self.break_remaining > 0 {
-                    self.break_remaining = self.break_remaining.saturating_sub(1);
-                    if let Some(ref mut screen) = self.break_screen {
-                        screen.update_remaining(self.break_remaining);
-                    }
-
-                    // If break time is up, end the break
-                    if self.break_remaining == 0 {
-                        if let Some(window_id) = self.break_window {
-                            self.break_window = None;
-                            self.break_screen = None;
-                            let timer = self.timer_service.clone();
-                            return Task::batch(vec![
-                                window::close(window_id),
-                                Task::perform(
-                                    async move {
-                                        timer.end_break().await;
-                                    },
-                                    |_| cosmic::Action::App(Message::Tick),
-                                ),
-                            ]);
-                        }
-                    }
-                }
-                Task::none()
-            }
         }
     }
 
@@ -447,26 +507,100 @@ impl cosmic::Application for CosmicEyes {
         let spacing = cosmic::theme::active().cosmic().spacing;
 
         // Format timer display
-        let short_text = if let Some(duration) = self.next_short_break {
-            format!("Short break: {}", Self::format_duration(duration))
-        } else {
-            "Short break: calculating...".to_string()
-        };
+        let mut timers = widget::column().spacing(spacing.space_s);
+        let mut next_at_text = "Next break at: calculating...".to_string();
 
-        let long_text = if let Some(duration) = self.next_long_break {
-            format!("Long break: {}", Self::format_duration(duration))
-        } else {
-            "Long break: calculating...".to_string()
-        };
+        match &self.snapshot {
+            None => {
+                timers = timers.push(widget::text("Breaks: calculating..."));
+            }
+            Some(snapshot) => {
+                for (name, duration) in &snapshot.breaks {
+                    timers = timers.push(widget::text(format!(
+                        "{} break: {}",
+                        name,
+                        Self::format_duration(*duration)
+                    )));
+                }
+                if let Some((pomodoro, cycles)) = snapshot.pomodoro {
+                    timers = timers.push(widget::text(format!(
+                        "Pomodoro {} of {}",
+                        pomodoro, cycles
+                    )));
+                }
+                if let Some((_, at)) = snapshot.next_break_at {
+                    next_at_text = format!("Next break at {}", at.format("%H:%M"));
+                }
+                if let Some(schedule) = snapshot.schedule {
+                    let hours = if schedule.working {
+                        "Working hours"
+                    } else {
+                        "Off hours"
+                    };
+                    timers = timers.push(widget::text(match schedule.until {
+                        Some(until) => format!("{} until {}", hours, until.format("%a %H:%M")),
+                        None => hours.to_string(),
+                    }));
+                }
+                if let Some(rule) = &snapshot.rule {
+                    timers = timers.push(widget::text(format!("Rule: {}", rule)));
+                }
+                for inhibitor in &snapshot.inhibitors {
+                    timers = timers.push(widget::text(format!(
+                        "Breaks held back by {}: {}",
+                        inhibitor.application, inhibitor.reason
+                    )));
+                }
+                match snapshot.idle {
+                    IdleStatus::Unused => {}
+                    IdleStatus::Unavailable => {
+                        timers = timers.push(widget::text("Idle detection: unavailable"));
+                    }
+                    IdleStatus::Backend(name) => {
+                        timers = timers.push(widget::text(format!("Idle detection: {}", name)));
+                    }
+                }
+            }
+        }
 
         let status_text = match &self.timer_state {
-            TimerState::Running => "Status: Active",
-            TimerState::Paused => "Status: Paused",
-            TimerState::InBreak(BreakType::Short) => "Status: In short break",
-            TimerState::InBreak(BreakType::Long) => "Status: In long break",
-            TimerState::Postponed => "Status: Break postponed",
+            TimerState::Running => "Status: Active".to_string(),
+            TimerState::Paused => match self.snapshot.as_ref().and_then(|s| s.paused_until) {
+                Some(until) => format!("Status: Paused until {}", until.format("%H:%M")),
+                None => "Status: Paused".to_string(),
+            },
+            TimerState::InBreak(name) => format!("Status: In {} break", name),
+            TimerState::Postponed(name) => format!("Status: {} break postponed", name),
+            TimerState::OffHours => "Status: Outside working hours".to_string(),
+            TimerState::Focus => match self.snapshot.as_ref() {
+                Some(snapshot) => {
+                    let until = snapshot
+                        .focus_until
+                        .map(|until| format!(" until {}", until.format("%H:%M")))
+                        .unwrap_or_default();
+                    format!(
+                        "Status: Focus mode{} (break debt {})",
+                        until,
+                        Self::format_duration(snapshot.break_debt)
+                    )
+                }
+                None => "Status: Focus mode".to_string(),
+            },
         };
 
+        let mut break_buttons = widget::row().spacing(spacing.space_s);
+        for break_config in self.config.enabled_breaks() {
+            break_buttons = break_buttons.push(
+                button::standard(break_config.name.clone())
+                    .on_press(Message::StartBreak(break_config.name.clone()))
+            );
+        }
+        break_buttons = break_buttons.push(if self.timer_state == TimerState::Focus {
+            button::standard("End focus").on_press(Message::EndFocus)
+        } else {
+            button::standard("Focus").on_press(Message::StartFocus)
+        });
+
         let content = widget::column()
             .spacing(spacing.space_m)
             .padding(spacing.space_m)
@@ -482,8 +616,8 @@ impl cosmic::Application for CosmicEyes {
             .push(
                 widget::column()
                     .spacing(spacing.space_s)
-                    .push(widget::text(short_text))
-                    .push(widget::text(long_text))
+                    .push(timers)
+                    .push(widget::text(next_at_text))
                     .push(widget::text(status_text))
             )
             .push(widget::divider::horizontal::default())
@@ -492,18 +626,7 @@ impl cosmic::Application for CosmicEyes {
                     .size(16)
                     .width(Length::Fill)
             )
-            .push(
-                widget::row()
-                    .spacing(spacing.space_s)
-                    .push(
-                        button::standard("Short Break")
-                            .on_press(Message::StartBreak(BreakType::Short))
-                    )
-                    .push(
-                        button::standard("Long Break")
-                            .on_press(Message::StartBreak(BreakType::Long))
-                    )
-            );
+            .push(break_buttons);
 
         widget::container(content)
             .width(Length::Fill)
@@ -513,11 +636,18 @@ impl cosmic::Application for CosmicEyes {
 
     fn subscription(&self) -> Subscription<Self::Message> {
         let mut subscriptions = vec![
-            // Timer tick every second
-            cosmic::iced::time::every(std::time::Duration::from_secs(1))
-                .map(|_| Message::Tick),
+            // Timer events, so state changes show up immediately
+            Self::timer_events(self.timer_service.subscribe()),
         ];
 
+        // Countdowns in the popup only need refreshing while it is shown
+        if self.popup.is_some() {
+            subscriptions.push(
+                cosmic::iced::time::every(std::time::Duration::from_secs(1))
+                    .map(|_| Message::Tick),
+            );
+        }
+
         // Add break countdown tick when in break
         if self.break_window.is_some() {
             subscriptions.push(
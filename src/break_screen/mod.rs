@@ -2,8 +2,6 @@ use cosmic::iced::Length;
 use cosmic::widget::{self, container};
 use cosmic::{app, Application, Element};
 
-use crate::timer::BreakType;
-
 /// Message types for the break screen
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -19,38 +17,42 @@ pub enum Message {
 
 /// Break screen state
 pub struct BreakScreen {
-    break_type: BreakType,
+    title: String,
+    message: String,
+    /// Where this break sits in a work cycle, e.g. "Pomodoro 2 of 4 complete"
+    progress: Option<String>,
     remaining_seconds: u64,
+    /// Whether input is holding up the countdown
+    interrupted: bool,
     allow_skip: bool,
     allow_postpone: bool,
+    /// Postpones left for this break, if they are limited
+    postpones_left: Option<u32>,
 }
 
 impl BreakScreen {
     pub fn new(
-        break_type: BreakType,
+        title: String,
+        message: String,
+        progress: Option<String>,
         duration_seconds: u64,
         allow_skip: bool,
         allow_postpone: bool,
+        postpones_left: Option<u32>,
     ) -> Self {
         Self {
-            break_type,
+            title,
+            message,
+            progress,
             remaining_seconds: duration_seconds,
+            interrupted: false,
             allow_skip,
             allow_postpone,
+            postpones_left,
         }
     }
 
     pub fn view(&self) -> Element<Message> {
-        let title = match self.break_type {
-            BreakType::Short => "Time for a short break!",
-            BreakType::Long => "Time for a long break!",
-        };
-
-        let message_text = match self.break_type {
-            BreakType::Short => "Look away from your screen and rest your eyes",
-            BreakType::Long => "Stand up, stretch, and take a walk",
-        };
-
         let minutes = self.remaining_seconds / 60;
         let seconds = self.remaining_seconds % 60;
         let time_text = format!("{:02}:{:02}", minutes, seconds);
@@ -59,21 +61,39 @@ impl BreakScreen {
             .spacing(20)
             .align_x(cosmic::iced::alignment::Horizontal::Center)
             .push(
-                widget::text(title)
+                widget::text(&self.title)
                     .size(48)
                     .width(Length::Shrink)
             )
             .push(
-                widget::text(message_text)
+                widget::text(&self.message)
                     .size(24)
                     .width(Length::Shrink)
-            )
+            );
+
+        if let Some(ref progress) = self.progress {
+            content = content.push(
+                widget::text(progress)
+                    .size(18)
+                    .width(Length::Shrink)
+            );
+        }
+
+        content = content
             .push(
                 widget::text(time_text)
                     .size(72)
                     .width(Length::Shrink)
             );
 
+        if self.interrupted {
+            content = content.push(
+                widget::text("Please step away from the screen")
+                    .size(24)
+                    .width(Length::Shrink)
+            );
+        }
+
         // Add buttons if allowed
         if self.allow_skip || self.allow_postpone {
             let mut buttons = widget::row().spacing(20);
@@ -95,6 +115,21 @@ impl BreakScreen {
             content = content.push(buttons);
         }
 
+        // Only worth showing while postponing is possible, or to explain
+        // why it no longer is
+        if let Some(left) = self.postpones_left.filter(|left| self.allow_postpone || *left == 0) {
+            let budget = match left {
+                0 => "No postpones left".to_string(),
+                1 => "1 postpone left".to_string(),
+                left => format!("{} postpones left", left),
+            };
+            content = content.push(
+                widget::text(budget)
+                    .size(18)
+                    .width(Length::Shrink)
+            );
+        }
+
         // Use styled container with solid background
         container(content)
             .width(Length::Fill)
@@ -108,4 +143,8 @@ impl BreakScreen {
     pub fn update_remaining(&mut self, seconds: u64) {
         self.remaining_seconds = seconds;
     }
+
+    pub fn set_interrupted(&mut self, interrupted: bool) {
+        self.interrupted = interrupted;
+    }
 }
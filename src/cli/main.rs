@@ -1,4 +1,7 @@
+use chrono::{Local, NaiveTime, TimeZone};
 use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use zbus::zvariant::OwnedValue;
 use zbus::{proxy, Connection};
 
 /// D-Bus proxy for timer interface
@@ -8,12 +11,19 @@ use zbus::{proxy, Connection};
     default_path = "/com/github/cosmiceyes"
 )]
 trait Timer {
-    async fn start_break(&self, break_type: &str) -> zbus::Result<()>;
+    async fn start_break(&self, break_name: &str) -> zbus::Result<()>;
     async fn skip_break(&self) -> zbus::Result<()>;
-    async fn postpone_break(&self, break_type: &str) -> zbus::Result<()>;
+    async fn postpone_break(&self, break_name: &str) -> zbus::Result<()>;
     async fn pause(&self) -> zbus::Result<()>;
+    async fn pause_for(&self, duration: u64) -> zbus::Result<()>;
+    async fn pause_until(&self, until: i64) -> zbus::Result<()>;
     async fn resume(&self) -> zbus::Result<()>;
-    async fn get_status(&self) -> zbus::Result<(String, i64, i64)>;
+    async fn start_focus(&self, duration: u64) -> zbus::Result<()>;
+    async fn end_focus(&self) -> zbus::Result<()>;
+    async fn inhibit(&self, application: &str, reason: &str) -> zbus::Result<u32>;
+    #[zbus(name = "UnInhibit")]
+    async fn uninhibit(&self, cookie: u32) -> zbus::Result<()>;
+    async fn get_status(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
 }
 
 /// CLI interface for Cosmic Eyes break reminder
@@ -29,9 +39,8 @@ struct Cli {
 enum Commands {
     /// Start a break immediately
     Break {
-        /// Type of break: "short" or "long"
-        #[arg(value_parser = ["short", "long"])]
-        break_type: String,
+        /// Name of the break kind, e.g. "short" or "long"
+        break_name: String,
     },
 
     /// Skip the current break
@@ -39,19 +48,48 @@ enum Commands {
 
     /// Postpone the next break
     Postpone {
-        /// Type of break to postpone: "short" or "long"
-        #[arg(value_parser = ["short", "long"])]
-        break_type: String,
+        /// Name of the break kind to postpone, e.g. "short" or "long"
+        break_name: String,
     },
 
     /// Show current status
     Status,
 
     /// Pause the timer
-    Pause,
+    Pause {
+        /// Resume automatically after this long, e.g. "45m", "1h30m" or "90s"
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration, conflicts_with = "until")]
+        duration: Option<u64>,
+
+        /// Resume automatically at this time of day, e.g. "14:00"
+        #[arg(long, value_name = "TIME")]
+        until: Option<NaiveTime>,
+    },
 
     /// Resume the timer
     Resume,
+
+    /// Hold back breaks for a focus block and catch up on them afterwards
+    Focus {
+        /// Length of the block, e.g. "2h" or "90m"; defaults to the configured one
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<u64>,
+
+        /// End the focus block now
+        #[arg(long, conflicts_with = "duration")]
+        end: bool,
+    },
+
+    /// Hold back breaks while a command runs, e.g. a presentation
+    Inhibit {
+        /// Why breaks are held back; defaults to the command line
+        #[arg(long)]
+        reason: Option<String>,
+
+        /// The command to run, after `--`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
 }
 
 #[tokio::main]
@@ -79,42 +117,119 @@ async fn main() {
 
     // Execute command
     let result = match cli.command {
-        Commands::Break { break_type } => {
-            proxy.start_break(&break_type).await
-                .map(|_| format!("Started {} break", break_type))
+        Commands::Break { break_name } => {
+            proxy.start_break(&break_name).await
+                .map(|_| format!("Started {} break", break_name))
         }
         Commands::Skip => {
             proxy.skip_break().await
                 .map(|_| "Skipped current break".to_string())
         }
-        Commands::Postpone { break_type } => {
-            proxy.postpone_break(&break_type).await
-                .map(|_| format!("Postponed {} break", break_type))
+        Commands::Postpone { break_name } => {
+            proxy.postpone_break(&break_name).await
+                .map(|_| format!("Postponed {} break", break_name))
         }
         Commands::Status => {
             match proxy.get_status().await {
-                Ok((state, short_secs, long_secs)) => {
-                    let state_display = match state.as_str() {
-                        "Running" => "Active",
-                        "Paused" => "Paused",
-                        "InBreak:Short" => "In short break",
-                        "InBreak:Long" => "In long break",
-                        "Postponed" => "Break postponed",
-                        _ => &state,
+                Ok(mut status) => {
+                    let state: String = take(&mut status, "state").unwrap_or_default();
+                    let breaks: Vec<(String, i64)> =
+                        take(&mut status, "breaks").unwrap_or_default();
+                    let postpones_left: HashMap<String, u32> =
+                        take::<Vec<(String, u32)>>(&mut status, "postpones_left")
+                            .unwrap_or_default()
+                            .into_iter()
+                            .collect();
+
+                    let state_display = match state.split_once(':') {
+                        Some(("InBreak", name)) => format!("In {} break", name),
+                        Some(("Postponed", name)) => format!("{} break postponed", name),
+                        _ => match state.as_str() {
+                            "Running" => "Active".to_string(),
+                            "Paused" => match take::<i64>(&mut status, "paused_until")
+                                .and_then(|at| Local.timestamp_opt(at, 0).single())
+                            {
+                                Some(until) => format!("Paused until {}", until.format("%H:%M")),
+                                None => "Paused".to_string(),
+                            },
+                            "OffHours" => "Outside working hours".to_string(),
+                            "Focus" => match take::<i64>(&mut status, "focus_until")
+                                .and_then(|at| Local.timestamp_opt(at, 0).single())
+                            {
+                                Some(until) => format!("Focus mode until {}", until.format("%H:%M")),
+                                None => "Focus mode".to_string(),
+                            },
+                            _ => state.clone(),
+                        },
                     };
 
-                    let short_display = format_duration(short_secs);
-                    let long_display = format_duration(long_secs);
+                    let mut lines = vec![format!("Status: {}", state_display)];
+                    if let Some((pomodoro, cycles)) = take::<(u32, u32)>(&mut status, "pomodoro") {
+                        lines.push(format!("Pomodoro: {} of {}", pomodoro, cycles));
+                    }
+                    if let Some(secs) = take::<i64>(&mut status, "break_debt") {
+                        lines.push(format!("Break debt: {}", format_duration(secs)));
+                    }
+                    if let Some(secs) = take::<i64>(&mut status, "break_remaining") {
+                        lines.push(format!("Break ends in: {}", format_duration(secs)));
+                    }
+                    for (name, secs) in breaks {
+                        let budget = match postpones_left.get(&name) {
+                            Some(0) => " (no postpones left)".to_string(),
+                            Some(1) => " (1 postpone left)".to_string(),
+                            Some(left) => format!(" ({} postpones left)", left),
+                            None => String::new(),
+                        };
+                        lines.push(format!(
+                            "Next {} break: {}{}",
+                            name,
+                            format_duration(secs),
+                            budget
+                        ));
+                    }
+                    if let Some(working) = take::<bool>(&mut status, "working") {
+                        let change = take::<i64>(&mut status, "schedule_change")
+                            .and_then(|at| Local.timestamp_opt(at, 0).single())
+                            .map(|at| at.format(" until %a %H:%M").to_string())
+                            .unwrap_or_default();
+                        let hours = if working { "working hours" } else { "off hours" };
+                        lines.push(format!("Schedule: {}{}", hours, change));
+                    }
+                    if let Some(rule) = take::<String>(&mut status, "rule") {
+                        lines.push(format!("Rule: {}", rule));
+                    }
+                    if let Some(backend) = take::<String>(&mut status, "idle_backend") {
+                        let backend = if backend.is_empty() { "unavailable" } else { &backend };
+                        lines.push(format!("Idle detection: {}", backend));
+                    }
+                    if take::<bool>(&mut status, "session_away").unwrap_or(false) {
+                        lines.push("Session locked: timers wait until it is back".to_string());
+                    }
+                    let inhibitors: Vec<(String, String)> =
+                        take(&mut status, "inhibitors").unwrap_or_default();
+                    for (application, reason) in inhibitors {
+                        lines.push(format!("Breaks held back by {}: {}", application, reason));
+                    }
 
-                    Ok(format!(
-                        "Status: {}\nNext short break: {}\nNext long break: {}",
-                        state_display, short_display, long_display
-                    ))
+                    Ok(lines.join("\n"))
                 }
                 Err(e) => Err(e),
             }
         }
-        Commands::Pause => {
+        Commands::Pause { duration: Some(secs), .. } => {
+            let until = Local::now() + chrono::Duration::seconds(secs as i64);
+            proxy.pause_for(secs).await
+                .map(|_| format!("Paused timer until {}", until.format("%H:%M")))
+        }
+        Commands::Pause { until: Some(time), .. } => match next_occurrence(time) {
+            Some(until) => proxy.pause_until(until.timestamp()).await
+                .map(|_| format!("Paused timer until {}", until.format("%a %H:%M"))),
+            None => {
+                eprintln!("{} does not exist today or tomorrow", time.format("%H:%M"));
+                std::process::exit(1);
+            }
+        },
+        Commands::Pause { .. } => {
             proxy.pause().await
                 .map(|_| "Paused timer".to_string())
         }
@@ -122,11 +237,40 @@ async fn main() {
             proxy.resume().await
                 .map(|_| "Resumed timer".to_string())
         }
+        Commands::Focus { end: true, .. } => {
+            proxy.end_focus().await
+                .map(|_| "Ended focus mode".to_string())
+        }
+        Commands::Focus { duration, .. } => {
+            proxy.start_focus(duration.unwrap_or(0)).await
+                .map(|_| "Started focus mode".to_string())
+        }
+        Commands::Inhibit { reason, command } => {
+            let application = std::path::Path::new(&command[0])
+                .file_name()
+                .map_or_else(|| command[0].clone(), |name| name.to_string_lossy().into_owned());
+            let reason = reason.unwrap_or_else(|| command.join(" "));
+            match proxy.inhibit(&application, &reason).await {
+                Ok(cookie) => {
+                    let code = run(command).await;
+                    // Exiting releases it as well; this just doesn't wait
+                    // for the applet to notice
+                    let _ = proxy.uninhibit(cookie).await;
+                    std::process::exit(code);
+                }
+                Err(e) => Err(e),
+            }
+        }
     };
 
     // Print result or error
     match result {
         Ok(msg) => println!("{}", msg),
+        // Errors raised by the applet carry a readable message
+        Err(zbus::Error::MethodError(_, Some(message), _)) => {
+            eprintln!("Command failed: {}", message);
+            std::process::exit(1);
+        }
         Err(e) => {
             eprintln!("Command failed: {}", e);
             std::process::exit(1);
@@ -134,6 +278,83 @@ async fn main() {
     }
 }
 
+/// Run a command to completion, returning its exit code
+async fn run(command: Vec<String>) -> i32 {
+    let program = command[0].clone();
+    let status = tokio::task::spawn_blocking(move || {
+        std::process::Command::new(&command[0]).args(&command[1..]).status()
+    })
+    .await;
+    match status {
+        Ok(Ok(status)) => status.code().unwrap_or(1),
+        Ok(Err(e)) => {
+            eprintln!("Failed to run {}: {}", program, e);
+            127
+        }
+        Err(_) => 1,
+    }
+}
+
+/// Remove and convert a field from a status dictionary
+fn take<T: TryFrom<OwnedValue>>(status: &mut HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    status.remove(key).and_then(|value| T::try_from(value).ok())
+}
+
+/// Parse a duration like "45m", "1h30m" or "90s" into seconds
+///
+/// A bare number is taken as minutes.
+fn parse_duration(text: &str) -> Result<u64, String> {
+    let too_long = || "duration is too long".to_string();
+    let total = match text.parse::<u64>() {
+        Ok(minutes) => minutes.checked_mul(60).ok_or_else(too_long)?,
+        Err(_) => {
+            let mut total: u64 = 0;
+            let mut number = String::new();
+            for c in text.chars() {
+                if c.is_ascii_digit() {
+                    number.push(c);
+                    continue;
+                }
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    's' => 1,
+                    _ => return Err(format!("unknown unit '{}', use h, m or s", c)),
+                };
+                if number.is_empty() {
+                    return Err(format!("missing number before '{}'", c));
+                }
+                total = number
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|value| value.checked_mul(unit))
+                    .and_then(|seconds| total.checked_add(seconds))
+                    .ok_or_else(too_long)?;
+                number.clear();
+            }
+
+            if !number.is_empty() {
+                return Err("missing unit after the last number".to_string());
+            }
+            total
+        }
+    };
+
+    if total == 0 {
+        return Err("duration must be longer than zero".to_string());
+    }
+    Ok(total)
+}
+
+/// The next time the clock shows `time`, today or tomorrow
+fn next_occurrence(time: NaiveTime) -> Option<chrono::DateTime<Local>> {
+    let now = Local::now();
+    [now.date_naive(), now.date_naive().succ_opt()?]
+        .into_iter()
+        .filter_map(|date| date.and_time(time).and_local_timezone(Local).earliest())
+        .find(|at| *at > now)
+}
+
 /// Format seconds into human-readable duration
 fn format_duration(seconds: i64) -> String {
     let minutes = seconds / 60;
@@ -145,3 +366,29 @@ fn format_duration(seconds: i64) -> String {
         format!("{}s", secs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45"), Ok(45 * 60));
+        assert_eq!(parse_duration("1h30m"), Ok(90 * 60));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("10").is_ok());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_zero_and_overflow() {
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0m0s").is_err());
+        assert!(parse_duration(&u64::MAX.to_string()).is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 3600 + 1)).is_err());
+        assert!(parse_duration(&format!("{}s1s", u64::MAX)).is_err());
+        assert!(parse_duration("99999999999999999999999m").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Time sources for the timer engine
+//!
+//! `TimerService` never reads the system time directly. Everything goes
+//! through a [`Clock`], so scheduling can be driven by a manually advanced
+//! clock in tests instead of waiting real minutes.
+//!
+//! A clock exposes two time lines: a monotonic one used for all scheduling,
+//! and the wall clock, which is only used to show the user when something
+//! will happen. On Linux the monotonic clock does not advance while the
+//! machine is suspended.
+
+use chrono::{DateTime, Local};
+use std::time::Instant;
+#[cfg(test)]
+use chrono::Duration;
+#[cfg(test)]
+use std::sync::Mutex;
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time
+    fn now(&self) -> DateTime<Local>;
+
+    /// Current monotonic time, unaffected by wall-clock adjustments
+    fn monotonic(&self) -> Instant;
+}
+
+/// Clock backed by the system time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to
+///
+/// Used to replay schedules deterministically, e.g. a full workday in
+/// milliseconds.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    inner: Mutex<ManualTime>,
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct ManualTime {
+    wall: DateTime<Local>,
+    monotonic: Instant,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    /// Create a clock frozen at `start`
+    pub fn new(start: DateTime<Local>) -> Self {
+        Self {
+            inner: Mutex::new(ManualTime {
+                wall: start,
+                monotonic: Instant::now(),
+            }),
+        }
+    }
+
+    /// Move both time lines forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.wall += duration;
+        inner.monotonic += duration.to_std().unwrap_or_default();
+    }
+
+    /// Jump the wall clock without moving monotonic time
+    ///
+    /// Simulates NTP corrections, manual clock changes and DST switches.
+    pub fn set(&self, time: DateTime<Local>) {
+        self.inner.lock().unwrap().wall = time;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        self.inner.lock().unwrap().wall
+    }
+
+    fn monotonic(&self) -> Instant {
+        self.inner.lock().unwrap().monotonic
+    }
+}
@@ -1,14 +1,44 @@
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Timelike, Weekday,
+};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Configuration for cosmic-eyes break reminder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    /// Short break settings
-    pub short_break: BreakConfig,
+    /// Break kinds, each scheduled independently
+    #[serde(default)]
+    pub breaks: Vec<BreakConfig>,
 
-    /// Long break settings
-    pub long_break: BreakConfig,
+    /// Legacy short break settings, migrated into `breaks` on load
+    #[serde(default, skip_serializing)]
+    short_break: Option<BreakConfig>,
+
+    /// Legacy long break settings, migrated into `breaks` on load
+    #[serde(default, skip_serializing)]
+    long_break: Option<BreakConfig>,
+
+    /// How breaks are scheduled
+    #[serde(default)]
+    pub mode: ScheduleMode,
+
+    /// Work-cycle settings used in `ScheduleMode::Pomodoro`
+    #[serde(default)]
+    pub pomodoro: PomodoroConfig,
+
+    /// Focus blocks that hold back breaks and repay them afterwards
+    #[serde(default)]
+    pub focus: FocusConfig,
+
+    /// When breaks are active at all
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+
+    /// Break settings that apply during parts of the day; the first
+    /// matching rule wins
+    #[serde(default)]
+    pub overrides: Vec<BreakOverride>,
 
     /// Whether to enable idle detection
     pub idle_detection: bool,
@@ -16,6 +46,23 @@ pub struct Config {
     /// Idle time threshold in seconds before pausing timers
     pub idle_threshold: u64,
 
+    /// Count only active time towards break intervals
+    #[serde(default)]
+    pub active_time_only: bool,
+
+    /// Idle gaps at least this many seconds long don't count as active time
+    #[serde(default = "default_idle_gap")]
+    pub idle_gap: u64,
+
+    /// What happens to break countdowns when the user returns from a pause
+    /// while they were away
+    #[serde(default)]
+    pub idle_return: IdleReturn,
+
+    /// What keyboard or mouse input during a break does
+    #[serde(default)]
+    pub break_activity: BreakActivity,
+
     /// Notification time before break starts (in seconds)
     pub notification_before_break: u64,
 
@@ -28,47 +75,618 @@ pub struct Config {
     /// Postpone duration in minutes
     pub postpone_duration: u64,
 
+    /// Postpone durations in minutes for the first, second, ... postpone
+    /// of a break; the last one repeats. Empty uses `postpone_duration`
+    #[serde(default)]
+    pub postpone_durations: Vec<u64>,
+
+    /// How often a break may be postponed before it is enforced like in
+    /// strict mode; `None` for no limit
+    #[serde(default)]
+    pub max_postpones: Option<u32>,
+
+    /// A break due this many minutes or less before a longer one is merged
+    /// into the longer one
+    #[serde(default = "default_merge_window")]
+    pub merge_window: u64,
+
     /// Enable strict mode (no skip/postpone)
     pub strict_mode: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BreakConfig {
+    /// Name used to address this break over D-Bus and the CLI
+    #[serde(default)]
+    pub name: String,
+
     /// Interval between breaks in minutes
+    ///
+    /// For clock-aligned kinds, the minimum time from the slot a break was
+    /// due at, or from a break taken early, to the next one, so a break
+    /// taken early doesn't come right back.
     pub interval: u64,
 
+    /// When breaks of this kind come due
+    #[serde(default)]
+    pub timing: BreakTiming,
+
     /// Duration of the break in seconds
     pub duration: u64,
 
-    /// Whether this break type is enabled
+    /// Whether this break kind is enabled
     pub enabled: bool,
+
+    /// Message shown on the break screen
+    #[serde(default)]
+    pub message: String,
+
+    /// Enforce this break (no skip/postpone) regardless of `strict_mode`
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// When breaks of a kind come due
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BreakTiming {
+    /// `interval` minutes after the last break of this kind
+    #[default]
+    Interval,
+    /// Every hour at this many minutes past the hour, e.g. `Hourly(50)`
+    Hourly(u32),
+    /// Every day at these times, e.g. `Daily(["10:30", "15:00"])`
+    Daily(Vec<NaiveTime>),
+}
+
+impl BreakTiming {
+    /// Whether breaks are aligned to the wall clock
+    pub fn is_aligned(&self) -> bool {
+        match self {
+            BreakTiming::Interval => false,
+            BreakTiming::Hourly(_) => true,
+            BreakTiming::Daily(times) => !times.is_empty(),
+        }
+    }
+
+    /// The first aligned slot at or after `time`, `None` for interval
+    /// timing
+    pub fn first_from(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.slots_around(time)?
+            .into_iter()
+            .filter(|at| *at >= time)
+            .min()
+    }
+
+    /// The last aligned slot at or before `time`, `None` for interval
+    /// timing
+    pub fn last_until(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.slots_around(time)?
+            .into_iter()
+            .filter(|at| *at <= time)
+            .max()
+    }
+
+    /// Aligned slots up to two hours or days either side of `time`
+    fn slots_around(&self, time: DateTime<Local>) -> Option<Vec<DateTime<Local>>> {
+        let local = time.naive_local();
+        let candidates: Vec<_> = match self {
+            BreakTiming::Interval => return None,
+            BreakTiming::Hourly(minute) => {
+                let hour = local.date().and_hms_opt(local.hour(), 0, 0)?;
+                let minute = Duration::minutes((*minute % 60).into());
+                // One extra hour each way for DST gaps
+                (-2..=2)
+                    .map(|h| hour + Duration::hours(h) + minute)
+                    .collect()
+            }
+            BreakTiming::Daily(times) => (-2..=2)
+                .filter_map(|d| local.date().checked_add_signed(Duration::days(d)))
+                .flat_map(|date| times.iter().map(move |t| date.and_time(*t)))
+                .collect(),
+        };
+
+        Some(
+            candidates
+                .into_iter()
+                .filter_map(|naive| Local.from_local_datetime(&naive).earliest())
+                .collect(),
+        )
+    }
+}
+
+/// What happens to break countdowns when the user returns from a pause
+/// while they were away
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdleReturn {
+    /// Countdowns of breaks at least as short as the time away start over;
+    /// the others go on as if the user had been there
+    #[default]
+    Reset,
+    /// Countdowns stood still while the user was away
+    Shift,
+    /// Like `Shift`, and a notification offers to count the time away as
+    /// a break instead
+    Ask,
+}
+
+/// What keyboard or mouse input during a break does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BreakActivity {
+    /// Nothing; the break counts down regardless
+    #[default]
+    Ignore,
+    /// The countdown stands still until the user stops again
+    Pause,
+    /// The countdown starts over once the user stops again
+    Restart,
+}
+
+/// How breaks are scheduled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleMode {
+    /// Every enabled break kind runs on its own interval
+    #[default]
+    Intervals,
+    /// Work blocks alternate with breaks, with a long break after each set
+    Pomodoro,
+}
+
+/// Classic Pomodoro work cycle
+///
+/// The breaks themselves are regular break kinds, referenced by name, so
+/// they keep their own duration, message and enforcement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PomodoroConfig {
+    /// Length of a work block in minutes
+    pub work: u64,
+
+    /// Break kind taken after each work block
+    pub short_break: String,
+
+    /// Break kind taken after the last work block of a set
+    pub long_break: String,
+
+    /// Number of work blocks in a set
+    pub cycles: u32,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work: 25,
+            short_break: "short".to_string(),
+            long_break: "long".to_string(),
+            cycles: 4,
+        }
+    }
+}
+
+impl PomodoroConfig {
+    /// Break kind that follows work block `pomodoro` (1-based) of a set
+    pub fn break_after(&self, pomodoro: u32) -> &str {
+        if pomodoro >= self.cycles {
+            &self.long_break
+        } else {
+            &self.short_break
+        }
+    }
+}
+
+/// Focus mode
+///
+/// Breaks missed during a focus block add up to break debt. Afterwards, a
+/// catch-up break of the longest kind missed repays it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusConfig {
+    /// Length of a focus block in minutes, unless given when starting one
+    pub duration: u64,
+
+    /// Length of the catch-up break as a percentage of the break debt
+    pub repay: u32,
+
+    /// Longest catch-up break in seconds
+    pub max_catch_up: u64,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            duration: 90,
+            repay: 100,
+            max_catch_up: 900, // 15 minutes
+        }
+    }
+}
+
+/// Working hours
+///
+/// Outside the working windows, and on holidays, the timer lies dormant.
+/// Without any windows breaks are active around the clock.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Working windows; empty means always active
+    #[serde(default)]
+    pub windows: Vec<WorkWindow>,
+
+    /// Dates without any working window, e.g. public holidays
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+}
+
+/// A working window repeated on some weekdays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkWindow {
+    /// Weekdays this window applies to, e.g. `["Mon", "Tue"]`; empty means
+    /// every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+
+    /// Start time of day, e.g. `"09:00"`
+    pub start: NaiveTime,
+
+    /// End time of day, after `start` on the same day
+    pub end: NaiveTime,
+}
+
+impl ScheduleConfig {
+    /// Whether breaks are active at `time`
+    pub fn is_active(&self, time: DateTime<Local>) -> bool {
+        if self.windows.is_empty() {
+            return true;
+        }
+
+        let date = time.date_naive();
+        if self.holidays.contains(&date) {
+            return false;
+        }
+
+        self.windows
+            .iter()
+            .any(|w| in_range(&w.days, w.start, w.end, time))
+    }
+
+    /// The next time after `time` at which `is_active` flips
+    ///
+    /// Looks up to a year ahead; `None` if the status never changes.
+    pub fn next_change(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let ranges: Vec<_> = self
+            .windows
+            .iter()
+            .map(|w| (w.days.as_slice(), w.start, w.end))
+            .collect();
+        next_flip(time, &ranges, |at| self.is_active(at))
+    }
+}
+
+/// Break settings for part of the day
+///
+/// Only the fields that are set replace those of the named break kinds,
+/// e.g. a shorter interval in the late afternoon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakOverride {
+    /// Name of the rule, shown in the status output
+    pub name: String,
+
+    /// Weekdays the rule applies to; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+
+    /// Start time of day, e.g. `"15:00"`
+    pub start: NaiveTime,
+
+    /// End time of day, after `start` on the same day
+    pub end: NaiveTime,
+
+    /// Changes to individual break kinds
+    pub breaks: Vec<BreakPatch>,
+}
+
+/// Changes to one break kind; unset fields keep their value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakPatch {
+    /// Name of the break kind to change
+    pub name: String,
+
+    #[serde(default)]
+    pub interval: Option<u64>,
+
+    #[serde(default)]
+    pub duration: Option<u64>,
+
+    #[serde(default)]
+    pub timing: Option<BreakTiming>,
+
+    #[serde(default)]
+    pub enabled: Option<bool>,
+
+    #[serde(default)]
+    pub message: Option<String>,
+
+    #[serde(default)]
+    pub strict: Option<bool>,
+}
+
+impl BreakPatch {
+    /// Apply the set fields to a break kind
+    fn apply(&self, break_config: &mut BreakConfig) {
+        if let Some(interval) = self.interval {
+            break_config.interval = interval;
+        }
+        if let Some(duration) = self.duration {
+            break_config.duration = duration;
+        }
+        if let Some(timing) = &self.timing {
+            break_config.timing = timing.clone();
+        }
+        if let Some(enabled) = self.enabled {
+            break_config.enabled = enabled;
+        }
+        if let Some(message) = &self.message {
+            break_config.message = message.clone();
+        }
+        if let Some(strict) = self.strict {
+            break_config.strict = strict;
+        }
+    }
+}
+
+/// Whether `time` falls into a daily range on the given weekdays
+///
+/// No weekdays means every day.
+fn in_range(days: &[Weekday], start: NaiveTime, end: NaiveTime, time: DateTime<Local>) -> bool {
+    let time_of_day = time.time();
+    (days.is_empty() || days.contains(&time.weekday())) && start <= time_of_day && time_of_day < end
+}
+
+/// The first start or end of a daily range after `time` at which `state`
+/// differs from its value at `time`
+///
+/// Looks up to a year ahead.
+fn next_flip<T: PartialEq>(
+    time: DateTime<Local>,
+    ranges: &[(&[Weekday], NaiveTime, NaiveTime)],
+    state: impl Fn(DateTime<Local>) -> T,
+) -> Option<DateTime<Local>> {
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let current = state(time);
+    let mut date = time.date_naive();
+    for _ in 0..=366 {
+        let mut boundaries: Vec<DateTime<Local>> = ranges
+            .iter()
+            .filter(|(days, _, _)| days.is_empty() || days.contains(&date.weekday()))
+            .flat_map(|(_, start, end)| [*start, *end])
+            .filter_map(|t| Local.from_local_datetime(&date.and_time(t)).earliest())
+            .filter(|at| *at > time)
+            .collect();
+        boundaries.sort();
+
+        if let Some(at) = boundaries.into_iter().find(|at| state(*at) != current) {
+            return Some(at);
+        }
+        date = date.succ_opt()?;
+    }
+
+    None
+}
+
+impl BreakConfig {
+    /// Title shown on the break screen
+    pub fn title(&self) -> String {
+        format!("Time for a {} break!", self.name)
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
-            short_break: BreakConfig {
-                interval: 20,  // Every 20 minutes
-                duration: 20,  // 20 seconds
-                enabled: true,
-            },
-            long_break: BreakConfig {
-                interval: 60,  // Every 60 minutes (1 hour)
-                duration: 300, // 5 minutes
-                enabled: true,
-            },
+            breaks: default_breaks(),
+            short_break: None,
+            long_break: None,
+            mode: ScheduleMode::default(),
+            pomodoro: PomodoroConfig::default(),
+            focus: FocusConfig::default(),
+            schedule: ScheduleConfig::default(),
+            overrides: Vec::new(),
             idle_detection: true,
             idle_threshold: 300, // 5 minutes
+            active_time_only: false,
+            idle_gap: default_idle_gap(),
+            idle_return: IdleReturn::default(),
+            break_activity: BreakActivity::default(),
             notification_before_break: 10, // 10 seconds warning
             allow_skip: true,
             allow_postpone: true,
             postpone_duration: 5, // 5 minutes
+            postpone_durations: Vec::new(),
+            max_postpones: None,
+            merge_window: default_merge_window(),
             strict_mode: false,
         }
     }
 }
 
+fn default_idle_gap() -> u64 {
+    60 // 1 minute
+}
+
+fn default_merge_window() -> u64 {
+    5 // 5 minutes
+}
+
+/// The classic short/long pair
+fn default_breaks() -> Vec<BreakConfig> {
+    vec![
+        BreakConfig {
+            name: "short".to_string(),
+            interval: 20,  // Every 20 minutes
+            duration: 20,  // 20 seconds
+            timing: BreakTiming::Interval,
+            enabled: true,
+            message: "Look away from your screen and rest your eyes".to_string(),
+            strict: false,
+        },
+        BreakConfig {
+            name: "long".to_string(),
+            interval: 60,  // Every 60 minutes (1 hour)
+            duration: 300, // 5 minutes
+            timing: BreakTiming::Interval,
+            enabled: true,
+            message: "Stand up, stretch, and take a walk".to_string(),
+            strict: false,
+        },
+    ]
+}
+
 impl Config {
+    /// Look up a break kind by name
+    pub fn break_config(&self, name: &str) -> Option<&BreakConfig> {
+        self.breaks.iter().find(|b| b.name == name)
+    }
+
+    /// Break kinds that are currently enabled
+    pub fn enabled_breaks(&self) -> impl Iterator<Item = &BreakConfig> {
+        self.breaks.iter().filter(|b| b.enabled)
+    }
+
+    /// The override rule active at `time`, if any
+    pub fn active_override(&self, time: DateTime<Local>) -> Option<&BreakOverride> {
+        self.overrides
+            .iter()
+            .find(|o| in_range(&o.days, o.start, o.end, time))
+    }
+
+    /// This configuration with the override rule active at `time` applied
+    pub fn at(&self, time: DateTime<Local>) -> Config {
+        let mut config = self.clone();
+        if let Some(rule) = self.active_override(time) {
+            for patch in &rule.breaks {
+                let target = config.breaks.iter_mut().find(|b| b.name == patch.name);
+                if let Some(break_config) = target {
+                    patch.apply(break_config);
+                }
+            }
+        }
+        config
+    }
+
+    /// The next time after `time` at which a different override rule
+    /// becomes active
+    pub fn next_override_change(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let ranges: Vec<_> = self
+            .overrides
+            .iter()
+            .map(|o| (o.days.as_slice(), o.start, o.end))
+            .collect();
+        next_flip(time, &ranges, |at| {
+            self.active_override(at).map(|o| o.name.as_str())
+        })
+    }
+
+    /// Whether skip/postpone are disabled for a break kind
+    pub fn is_strict(&self, name: &str) -> bool {
+        self.strict_mode || self.break_config(name).is_some_and(|b| b.strict)
+    }
+
+    /// Minutes to postpone a break by that was postponed `postponed` times
+    /// already
+    pub fn postpone_minutes(&self, postponed: u32) -> u64 {
+        self.postpone_durations
+            .get(postponed as usize)
+            .or(self.postpone_durations.last())
+            .copied()
+            .unwrap_or(self.postpone_duration)
+    }
+
+    /// Postpones left for a break that was postponed `postponed` times
+    /// already, or `None` without a limit
+    pub fn postpones_left(&self, postponed: u32) -> Option<u32> {
+        self.max_postpones.map(|max| max.saturating_sub(postponed))
+    }
+
+    /// Fold legacy `short_break`/`long_break` settings into `breaks`
+    ///
+    /// Only the loaded configuration changes; the file keeps the user's
+    /// own layout and comments.
+    fn migrate(&mut self) {
+        let legacy = [
+            ("short", self.short_break.take()),
+            ("long", self.long_break.take()),
+        ];
+
+        for (name, legacy) in legacy {
+            let Some(mut legacy) = legacy else { continue };
+            if self.break_config(name).is_some() {
+                continue;
+            }
+            let defaults = default_breaks().into_iter().find(|b| b.name == name);
+            legacy.name = name.to_string();
+            if legacy.message.is_empty() {
+                legacy.message = defaults.map(|b| b.message).unwrap_or_default();
+            }
+            self.breaks.push(legacy);
+        }
+
+        if self.breaks.is_empty() {
+            self.breaks = default_breaks();
+        }
+    }
+
+    /// Check what the file format can't, such as that every break kind
+    /// has a name of its own and the Pomodoro cycle only names existing ones
+    fn validate(&self) -> Result<(), String> {
+        for (i, b) in self.breaks.iter().enumerate() {
+            if b.name.is_empty() {
+                return Err(format!("break {} has no name", i + 1));
+            }
+            if self.breaks[..i].iter().any(|other| other.name == b.name) {
+                return Err(format!("more than one break is named {:?}", b.name));
+            }
+            // It would come due again as soon as it ended
+            if b.interval == 0 && !b.timing.is_aligned() {
+                return Err(format!("the {} break has an interval of 0 minutes", b.name));
+            }
+        }
+
+        for window in &self.schedule.windows {
+            if window.start >= window.end {
+                return Err(format!(
+                    "working window {} to {} doesn't end after it starts",
+                    window.start.format("%H:%M"),
+                    window.end.format("%H:%M")
+                ));
+            }
+        }
+
+        for rule in &self.overrides {
+            if rule.start >= rule.end {
+                return Err(format!(
+                    "override {} doesn't end after it starts",
+                    rule.name
+                ));
+            }
+        }
+
+        if self.pomodoro.cycles == 0 {
+            return Err("pomodoro.cycles must be at least 1".to_string());
+        }
+        if self.pomodoro.work == 0 {
+            return Err("pomodoro.work must be at least 1 minute".to_string());
+        }
+        // Interval scheduling doesn't use them, so they may keep naming
+        // kinds that were renamed since
+        if self.mode == ScheduleMode::Pomodoro {
+            for name in [&self.pomodoro.short_break, &self.pomodoro.long_break] {
+                if self.break_config(name).is_none() {
+                    return Err(format!("pomodoro names unknown break {:?}", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Get the config file path
     pub fn config_path() -> PathBuf {
         let config_dir = dirs::config_dir()
@@ -85,7 +703,11 @@ impl Config {
 
         if path.exists() {
             let contents = std::fs::read_to_string(&path)?;
-            let config = ron::from_str(&contents)?;
+            let mut config: Self = ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+                .from_str(&contents)?;
+            config.migrate();
+            config.validate()?;
             Ok(config)
         } else {
             // Create default config
@@ -106,3 +728,245 @@ impl Config {
 
 // Additional dependency needed - add to Cargo.toml:
 // dirs = "5.0"
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Local time on a day of October 2026; the 19th is a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    /// Working hours on weekdays, with a holiday on Tuesday the 20th
+    fn office_hours() -> ScheduleConfig {
+        ScheduleConfig {
+            windows: vec![WorkWindow {
+                days: vec![
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ],
+                start: time(9, 0),
+                end: time(17, 30),
+            }],
+            holidays: vec![NaiveDate::from_ymd_opt(2026, 10, 20).unwrap()],
+        }
+    }
+
+    /// A break kind every `interval` minutes
+    fn kind(name: &str, interval: u64) -> BreakConfig {
+        BreakConfig {
+            name: name.to_string(),
+            interval,
+            timing: BreakTiming::Interval,
+            duration: 20,
+            enabled: true,
+            message: String::new(),
+            strict: false,
+        }
+    }
+
+    /// The default configuration with these break kinds
+    fn with_breaks(breaks: Vec<BreakConfig>) -> Config {
+        Config {
+            breaks,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_migrate_names_legacy_breaks() {
+        let mut config = Config {
+            short_break: Some(kind("", 15)),
+            ..with_breaks(Vec::new())
+        };
+        config.migrate();
+
+        assert_eq!(config.breaks.len(), 1);
+        assert_eq!(config.breaks[0].name, "short");
+        assert_eq!(config.breaks[0].interval, 15);
+        assert!(!config.breaks[0].message.is_empty());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_migrate_keeps_configured_kinds() {
+        let mut config = Config {
+            long_break: Some(kind("", 90)),
+            ..Config::default()
+        };
+        config.migrate();
+
+        let intervals: Vec<_> = config.breaks.iter().map(|b| b.interval).collect();
+        assert_eq!(intervals, [20, 60]);
+    }
+
+    #[test]
+    fn test_validate_rejects_unnamed_and_duplicate_breaks() {
+        let valid = |breaks: Vec<BreakConfig>| with_breaks(breaks).validate().is_ok();
+        assert!(!valid(vec![kind("short", 20), kind("", 60)]));
+        assert!(!valid(vec![kind("eyes", 20), kind("eyes", 60)]));
+        assert!(valid(vec![kind("eyes", 20), kind("walk", 60)]));
+    }
+
+    #[test]
+    fn test_schedule_windows_and_holidays() {
+        let schedule = office_hours();
+        assert!(!schedule.is_active(at(19, 8, 59)));
+        assert!(schedule.is_active(at(19, 9, 0)));
+        assert!(schedule.is_active(at(19, 17, 29)));
+        // Windows end before their end time
+        assert!(!schedule.is_active(at(19, 17, 30)));
+        assert!(!schedule.is_active(at(20, 10, 0)));
+        assert!(!schedule.is_active(at(24, 10, 0)));
+    }
+
+    #[test]
+    fn test_schedule_next_change_skips_holidays_and_weekends() {
+        let schedule = office_hours();
+        assert_eq!(schedule.next_change(at(19, 10, 0)), Some(at(19, 17, 30)));
+        assert_eq!(schedule.next_change(at(19, 17, 30)), Some(at(21, 9, 0)));
+        assert_eq!(schedule.next_change(at(23, 18, 0)), Some(at(26, 9, 0)));
+    }
+
+    #[test]
+    fn test_schedule_without_windows_is_always_active() {
+        let schedule = ScheduleConfig::default();
+        assert!(schedule.is_active(at(24, 3, 0)));
+        assert_eq!(schedule.next_change(at(24, 3, 0)), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_windows_across_midnight() {
+        let mut config = Config {
+            schedule: office_hours(),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.schedule.windows[0].start = time(22, 0);
+        config.schedule.windows[0].end = time(2, 0);
+        assert!(config.validate().is_err());
+    }
+
+    /// Every day from 15:00 to 18:00, short breaks come every 40 minutes
+    fn afternoon() -> BreakOverride {
+        BreakOverride {
+            name: "afternoon".to_string(),
+            days: Vec::new(),
+            start: time(15, 0),
+            end: time(18, 0),
+            breaks: vec![BreakPatch {
+                name: "short".to_string(),
+                interval: Some(40),
+                duration: None,
+                timing: None,
+                enabled: None,
+                message: None,
+                strict: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_override_patches_breaks_inside_its_window() {
+        let mut config = with_breaks(vec![kind("short", 20), kind("long", 60)]);
+        config.overrides.push(afternoon());
+
+        let interval = |config: Config, name| config.break_config(name).unwrap().interval;
+        assert_eq!(interval(config.at(at(19, 14, 59)), "short"), 20);
+        assert_eq!(interval(config.at(at(19, 15, 0)), "short"), 40);
+        assert_eq!(interval(config.at(at(19, 15, 0)), "long"), 60);
+        assert_eq!(interval(config.at(at(19, 18, 0)), "short"), 20);
+    }
+
+    #[test]
+    fn test_next_override_change() {
+        let mut config = with_breaks(vec![kind("short", 20)]);
+        assert_eq!(config.next_override_change(at(19, 10, 0)), None);
+
+        config.overrides.push(afternoon());
+        let next = |time| config.next_override_change(time);
+        assert_eq!(next(at(19, 10, 0)), Some(at(19, 15, 0)));
+        assert_eq!(next(at(19, 15, 0)), Some(at(19, 18, 0)));
+        assert_eq!(next(at(19, 18, 0)), Some(at(20, 15, 0)));
+    }
+
+    #[test]
+    fn test_validate_rejects_overrides_across_midnight() {
+        let mut config = with_breaks(vec![kind("short", 20)]);
+        config.overrides.push(afternoon());
+        assert!(config.validate().is_ok());
+
+        config.overrides[0].end = time(2, 0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_checks_pomodoro_settings() {
+        let mut config = Config {
+            mode: ScheduleMode::Pomodoro,
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.pomodoro.long_break = "stretch".to_string();
+        assert!(config.validate().is_err());
+        config.mode = ScheduleMode::Intervals;
+        assert!(config.validate().is_ok());
+
+        for (cycles, work) in [(0, 25), (4, 0)] {
+            config.pomodoro = PomodoroConfig {
+                cycles,
+                work,
+                ..PomodoroConfig::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_interval_unless_aligned() {
+        let mut hourly = kind("hourly", 0);
+        assert!(with_breaks(vec![hourly.clone()]).validate().is_err());
+
+        hourly.timing = BreakTiming::Hourly(50);
+        assert!(with_breaks(vec![hourly]).validate().is_ok());
+    }
+
+    #[test]
+    fn test_hourly_slots() {
+        let timing = BreakTiming::Hourly(50);
+        assert_eq!(timing.first_from(at(19, 10, 0)), Some(at(19, 10, 50)));
+        assert_eq!(timing.first_from(at(19, 10, 50)), Some(at(19, 10, 50)));
+        assert_eq!(timing.first_from(at(19, 10, 51)), Some(at(19, 11, 50)));
+        assert_eq!(timing.last_until(at(19, 10, 50)), Some(at(19, 10, 50)));
+        assert_eq!(timing.last_until(at(19, 10, 49)), Some(at(19, 9, 50)));
+    }
+
+    #[test]
+    fn test_daily_slots_reach_into_other_days() {
+        let timing = BreakTiming::Daily(vec![time(10, 30), time(15, 0)]);
+        assert_eq!(timing.first_from(at(19, 12, 0)), Some(at(19, 15, 0)));
+        assert_eq!(timing.first_from(at(19, 16, 0)), Some(at(20, 10, 30)));
+        assert_eq!(timing.last_until(at(19, 9, 0)), Some(at(18, 15, 0)));
+    }
+
+    #[test]
+    fn test_interval_timing_has_no_slots() {
+        for timing in [BreakTiming::Interval, BreakTiming::Daily(Vec::new())] {
+            assert!(!timing.is_aligned());
+            assert_eq!(timing.first_from(at(19, 10, 0)), None);
+            assert_eq!(timing.last_until(at(19, 10, 0)), None);
+        }
+    }
+}
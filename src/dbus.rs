@@ -2,9 +2,15 @@
 //!
 //! Provides IPC between CLI and applet using D-Bus.
 
-use crate::timer::{BreakType, TimerService, TimerState};
+use crate::timer::{IdleStatus, TimerError, TimerEvent, TimerService, TimerState};
+use chrono::{Local, TimeZone};
+use futures_util::StreamExt;
+use std::collections::HashMap;
 use std::sync::Arc;
-use zbus::{interface, ConnectionBuilder};
+use tokio::sync::broadcast::error::RecvError;
+use zbus::message::Header;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{interface, Connection, SignalContext};
 
 const SERVICE_NAME: &str = "com.github.cosmiceyes";
 const OBJECT_PATH: &str = "/com/github/cosmiceyes";
@@ -20,73 +26,430 @@ impl TimerInterface {
     }
 }
 
+/// Errors returned over D-Bus, named `com.github.cosmiceyes.Error.*`
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "com.github.cosmiceyes.Error")]
+pub enum Error {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// No break kind with this name is configured
+    UnknownBreak(String),
+    /// The break kind has no upcoming deadline
+    NotScheduled(String),
+    /// The action is not allowed in the current timer state
+    InvalidTransition(String),
+    /// The break used up its postpones and must be taken
+    PostponeLimit(String),
+    /// A timestamp argument is out of range
+    InvalidTime(String),
+    /// No inhibitor holds the cookie
+    UnknownInhibitor(String),
+    /// No time away is waiting to count as a break
+    NoPendingReturn(String),
+}
+
+impl From<TimerError> for Error {
+    fn from(error: TimerError) -> Self {
+        let message = error.to_string();
+        match error {
+            TimerError::UnknownBreak(_) => Error::UnknownBreak(message),
+            TimerError::NotScheduled(_) => Error::NotScheduled(message),
+            TimerError::InvalidTransition { .. } => Error::InvalidTransition(message),
+            TimerError::PostponeLimit(_) => Error::PostponeLimit(message),
+            TimerError::UnknownInhibitor(_) => Error::UnknownInhibitor(message),
+            TimerError::NoPendingReturn => Error::NoPendingReturn(message),
+        }
+    }
+}
+
+/// State string used on the bus, e.g. `Running` or `InBreak:short`
+fn state_str(state: &TimerState) -> String {
+    match state {
+        TimerState::Running => "Running".to_string(),
+        TimerState::Paused => "Paused".to_string(),
+        TimerState::InBreak(name) => format!("InBreak:{}", name),
+        TimerState::Postponed(name) => format!("Postponed:{}", name),
+        TimerState::OffHours => "OffHours".to_string(),
+        TimerState::Focus => "Focus".to_string(),
+    }
+}
+
 #[interface(name = "com.github.cosmiceyes.Timer")]
 impl TimerInterface {
-    /// Start a break immediately
-    async fn start_break(&self, break_type: &str) -> zbus::fdo::Result<()> {
-        let bt = match break_type {
-            "short" => BreakType::Short,
-            "long" => BreakType::Long,
-            _ => return Err(zbus::fdo::Error::InvalidArgs("Invalid break type".to_string())),
-        };
-        self.timer.start_break(bt).await;
+    /// Start a break of the named kind immediately
+    async fn start_break(&self, break_name: &str) -> Result<(), Error> {
+        self.timer.start_break(break_name).await?;
         Ok(())
     }
 
     /// Skip current break
-    async fn skip_break(&self) -> zbus::fdo::Result<()> {
-        self.timer.skip_break().await;
+    async fn skip_break(&self) -> Result<(), Error> {
+        self.timer.skip_break().await?;
         Ok(())
     }
 
-    /// Postpone next break
-    async fn postpone_break(&self, break_type: &str) -> zbus::fdo::Result<()> {
-        let bt = match break_type {
-            "short" => BreakType::Short,
-            "long" => BreakType::Long,
-            _ => return Err(zbus::fdo::Error::InvalidArgs("Invalid break type".to_string())),
-        };
-        self.timer.postpone_break(bt).await;
+    /// Postpone the next break of the named kind
+    async fn postpone_break(&self, break_name: &str) -> Result<(), Error> {
+        self.timer.postpone_break(break_name).await?;
         Ok(())
     }
 
     /// Pause timers
-    async fn pause(&self) -> zbus::fdo::Result<()> {
-        self.timer.pause().await;
+    async fn pause(&self) -> Result<(), Error> {
+        self.timer.pause().await?;
+        Ok(())
+    }
+
+    /// Pause timers for `duration` seconds, then resume automatically
+    async fn pause_for(&self, duration: u64) -> Result<(), Error> {
+        self.timer.pause_for(std::time::Duration::from_secs(duration)).await?;
+        Ok(())
+    }
+
+    /// Pause timers until the given Unix time, then resume automatically
+    async fn pause_until(&self, until: i64) -> Result<(), Error> {
+        let until = Local
+            .timestamp_opt(until, 0)
+            .single()
+            .ok_or_else(|| Error::InvalidTime(format!("Invalid Unix time: {}", until)))?;
+        self.timer.pause_until(until).await?;
+        Ok(())
+    }
+
+    /// Hold back breaks for `duration` seconds, or the configured focus
+    /// block length if 0
+    async fn start_focus(&self, duration: u64) -> Result<(), Error> {
+        let duration = (duration > 0).then_some(std::time::Duration::from_secs(duration));
+        self.timer.start_focus(duration).await?;
+        Ok(())
+    }
+
+    /// End focus mode early, starting the catch-up break if breaks were
+    /// missed
+    async fn end_focus(&self) -> Result<(), Error> {
+        self.timer.end_focus().await?;
+        Ok(())
+    }
+
+    /// Count the time away announced by the latest `IdleReturned` as a
+    /// break after all
+    async fn count_away_as_break(&self) -> Result<(), Error> {
+        self.timer.count_away_as_break().await?;
+        Ok(())
+    }
+
+    /// Hold back breaks, e.g. during a presentation, until `UnInhibit` is
+    /// called with the returned cookie or the caller leaves the bus
+    ///
+    /// Mirrors `org.freedesktop.ScreenSaver.Inhibit`.
+    async fn inhibit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        application: String,
+        reason: String,
+    ) -> u32 {
+        let owner = header.sender().map(|sender| sender.to_string());
+        self.timer.inhibit(application, reason, owner).await
+    }
+
+    /// Release an inhibitor by the cookie `Inhibit` returned
+    #[zbus(name = "UnInhibit")]
+    async fn uninhibit(&self, cookie: u32) -> Result<(), Error> {
+        self.timer.uninhibit(cookie).await?;
         Ok(())
     }
 
     /// Resume timers
-    async fn resume(&self) -> zbus::fdo::Result<()> {
-        self.timer.resume().await;
+    async fn resume(&self) -> Result<(), Error> {
+        self.timer.resume().await?;
         Ok(())
     }
 
     /// Get current status
-    async fn get_status(&self) -> zbus::fdo::Result<(String, i64, i64)> {
-        let state = self.timer.state().await;
-        let short_remaining = self.timer.time_until_short_break().await.num_seconds();
-        let long_remaining = self.timer.time_until_long_break().await.num_seconds();
+    ///
+    /// All values come from a single timer snapshot. Keys:
+    /// - `state` (s): timer state, e.g. `Running` or `InBreak:<name>`
+    /// - `breaks` (a(sx)): seconds until each scheduled break kind
+    /// - `pomodoro` ((uu)): current work block and blocks per set, only
+    ///   present in Pomodoro mode
+    /// - `break_remaining` (x): seconds left in the active break, only
+    ///   present during a break
+    /// - `break_interrupted` (b): whether input is holding up the active
+    ///   break, only present during a break
+    /// - `postpones_left` (a(su)): postpones left per scheduled break kind,
+    ///   only present if they are limited
+    /// - `paused_until` (x): Unix time at which a timed pause ends
+    /// - `focus_until` (x): Unix time at which focus mode ends, only present
+    ///   in focus mode with an end
+    /// - `break_debt` (x): seconds of breaks missed in focus mode, only
+    ///   present in focus mode
+    /// - `idle_backend` (s): idle detection backend in use, empty if none
+    ///   works; only present while idle detection is needed
+    /// - `session_away` (b): `true` while the session is locked or switched
+    ///   away from, otherwise absent
+    /// - `inhibitors` (a(ss)): application and reason of each inhibitor,
+    ///   only present while breaks are inhibited
+    /// - `working` (b): whether it is working hours, only present if a
+    ///   schedule is configured
+    /// - `schedule_change` (x): Unix time at which `working` flips next
+    /// - `rule` (s): active time-of-day override rule, only present while
+    ///   one applies
+    async fn get_status(&self) -> Result<HashMap<String, OwnedValue>, Error> {
+        let snapshot = self.timer.snapshot().await;
+        let breaks: Vec<(String, i64)> = snapshot
+            .breaks
+            .iter()
+            .map(|(name, remaining)| (name.clone(), remaining.num_seconds()))
+            .collect();
+
+        let mut status = HashMap::new();
+        status.insert("state".to_string(), owned(state_str(&snapshot.state))?);
+        status.insert("breaks".to_string(), owned(breaks)?);
+        if let Some(position) = snapshot.pomodoro {
+            status.insert("pomodoro".to_string(), owned(position)?);
+        }
+        if let Some(remaining) = snapshot.break_remaining {
+            status.insert("break_remaining".to_string(), owned(remaining.num_seconds())?);
+            status.insert("break_interrupted".to_string(), owned(snapshot.break_interrupted)?);
+        }
+        if !snapshot.postpones_left.is_empty() {
+            status.insert("postpones_left".to_string(), owned(snapshot.postpones_left)?);
+        }
+        if let Some(until) = snapshot.paused_until {
+            status.insert("paused_until".to_string(), owned(until.timestamp())?);
+        }
+        if snapshot.state == TimerState::Focus {
+            if let Some(until) = snapshot.focus_until {
+                status.insert("focus_until".to_string(), owned(until.timestamp())?);
+            }
+            status.insert("break_debt".to_string(), owned(snapshot.break_debt.num_seconds())?);
+        }
+        match snapshot.idle {
+            IdleStatus::Unused => {}
+            IdleStatus::Unavailable => {
+                status.insert("idle_backend".to_string(), owned("")?);
+            }
+            IdleStatus::Backend(name) => {
+                status.insert("idle_backend".to_string(), owned(name)?);
+            }
+        }
+        if snapshot.session_away {
+            status.insert("session_away".to_string(), owned(true)?);
+        }
+        if !snapshot.inhibitors.is_empty() {
+            let inhibitors: Vec<(String, String)> = snapshot
+                .inhibitors
+                .into_iter()
+                .map(|inhibitor| (inhibitor.application, inhibitor.reason))
+                .collect();
+            status.insert("inhibitors".to_string(), owned(inhibitors)?);
+        }
+        if let Some(schedule) = snapshot.schedule {
+            status.insert("working".to_string(), owned(schedule.working)?);
+            if let Some(until) = schedule.until {
+                status.insert("schedule_change".to_string(), owned(until.timestamp())?);
+            }
+        }
+        if let Some(rule) = snapshot.rule {
+            status.insert("rule".to_string(), owned(rule)?);
+        }
 
-        let state_str = match state {
-            TimerState::Running => "Running",
-            TimerState::Paused => "Paused",
-            TimerState::InBreak(BreakType::Short) => "InBreak:Short",
-            TimerState::InBreak(BreakType::Long) => "InBreak:Long",
-            TimerState::Postponed => "Postponed",
-        };
+        Ok(status)
+    }
+
+    /// A break's deadline passed
+    #[zbus(signal)]
+    async fn break_due(ctxt: &SignalContext<'_>, break_name: &str) -> zbus::Result<()>;
+
+    /// A break started, lasting `duration` seconds
+    #[zbus(signal)]
+    async fn break_started(
+        ctxt: &SignalContext<'_>,
+        break_name: &str,
+        duration: u64,
+    ) -> zbus::Result<()>;
+
+    /// A break ran its full length
+    #[zbus(signal)]
+    async fn break_ended(ctxt: &SignalContext<'_>, break_name: &str) -> zbus::Result<()>;
+
+    /// A break was skipped
+    #[zbus(signal)]
+    async fn break_skipped(ctxt: &SignalContext<'_>, break_name: &str) -> zbus::Result<()>;
+
+    /// A break was postponed by `by` seconds
+    #[zbus(signal)]
+    async fn break_postponed(
+        ctxt: &SignalContext<'_>,
+        break_name: &str,
+        by: u64,
+    ) -> zbus::Result<()>;
+
+    /// Timers were paused
+    #[zbus(signal)]
+    async fn paused(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// Timers were resumed
+    #[zbus(signal)]
+    async fn resumed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// A timed pause ran out and timers resumed
+    #[zbus(signal)]
+    async fn pause_expired(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// Focus mode started, lasting until the given Unix time or 0 until
+    /// ended
+    #[zbus(signal)]
+    async fn focus_started(ctxt: &SignalContext<'_>, until: i64) -> zbus::Result<()>;
+
+    /// Focus mode ended; `catch_up` is the length in seconds of the
+    /// catch-up break that follows, 0 if none
+    #[zbus(signal)]
+    async fn focus_ended(ctxt: &SignalContext<'_>, catch_up: u64) -> zbus::Result<()>;
+
+    /// A new configuration was applied
+    #[zbus(signal)]
+    async fn config_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// A break starts in `remaining` seconds
+    #[zbus(signal)]
+    async fn pre_break_warning(
+        ctxt: &SignalContext<'_>,
+        break_name: &str,
+        remaining: u64,
+    ) -> zbus::Result<()>;
+
+    /// Working hours ended
+    #[zbus(signal)]
+    async fn clocked_out(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// Working hours started
+    #[zbus(signal)]
+    async fn clocked_in(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
 
-        Ok((state_str.to_string(), short_remaining, long_remaining))
+    /// The user was away for `idle` seconds, counted as a break
+    #[zbus(signal)]
+    async fn natural_break(
+        ctxt: &SignalContext<'_>,
+        break_name: &str,
+        idle: u64,
+    ) -> zbus::Result<()>;
+
+    /// Input during a break held up its countdown
+    #[zbus(signal)]
+    async fn break_interrupted(ctxt: &SignalContext<'_>, break_name: &str) -> zbus::Result<()>;
+
+    /// The break countdown goes on after input stopped
+    #[zbus(signal)]
+    async fn break_continued(ctxt: &SignalContext<'_>, break_name: &str) -> zbus::Result<()>;
+
+    /// The session was locked or switched away from; timers are paused
+    #[zbus(signal)]
+    async fn session_away(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// The session is unlocked and active again
+    #[zbus(signal)]
+    async fn session_back(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// The user came back after being away this many seconds, long enough
+    /// to count as a break if `CountAwayAsBreak` is called within two
+    /// minutes
+    #[zbus(signal)]
+    async fn idle_returned(ctxt: &SignalContext<'_>, away: u64) -> zbus::Result<()>;
+
+    /// An inhibitor was added or released
+    #[zbus(signal)]
+    async fn inhibitors_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    /// A different override rule applies; empty if none does
+    #[zbus(signal)]
+    async fn rule_changed(ctxt: &SignalContext<'_>, rule: &str) -> zbus::Result<()>;
+}
+
+/// Emit the D-Bus signal matching a timer event
+async fn emit(ctxt: &SignalContext<'_>, event: &TimerEvent) -> zbus::Result<()> {
+    match event {
+        TimerEvent::BreakDue(name) => TimerInterface::break_due(ctxt, name).await,
+        TimerEvent::BreakStarted { name, duration } => {
+            TimerInterface::break_started(ctxt, name, duration.as_secs()).await
+        }
+        TimerEvent::BreakEnded(name) => TimerInterface::break_ended(ctxt, name).await,
+        TimerEvent::Skipped(name) => TimerInterface::break_skipped(ctxt, name).await,
+        TimerEvent::Postponed { name, by } => {
+            TimerInterface::break_postponed(ctxt, name, by.as_secs()).await
+        }
+        TimerEvent::Paused => TimerInterface::paused(ctxt).await,
+        TimerEvent::Resumed => TimerInterface::resumed(ctxt).await,
+        TimerEvent::PauseExpired => TimerInterface::pause_expired(ctxt).await,
+        TimerEvent::FocusStarted(until) => {
+            TimerInterface::focus_started(ctxt, until.map_or(0, |until| until.timestamp())).await
+        }
+        TimerEvent::FocusEnded(catch_up) => {
+            TimerInterface::focus_ended(ctxt, catch_up.map_or(0, |d| d.as_secs())).await
+        }
+        TimerEvent::ConfigChanged => TimerInterface::config_changed(ctxt).await,
+        TimerEvent::PreBreakWarning { name, remaining } => {
+            TimerInterface::pre_break_warning(ctxt, name, remaining.as_secs()).await
+        }
+        TimerEvent::ClockedOut => TimerInterface::clocked_out(ctxt).await,
+        TimerEvent::ClockedIn => TimerInterface::clocked_in(ctxt).await,
+        TimerEvent::NaturalBreak { name, idle } => {
+            TimerInterface::natural_break(ctxt, name, idle.as_secs()).await
+        }
+        TimerEvent::BreakInterrupted(name) => TimerInterface::break_interrupted(ctxt, name).await,
+        TimerEvent::BreakContinued(name) => TimerInterface::break_continued(ctxt, name).await,
+        TimerEvent::SessionAway => TimerInterface::session_away(ctxt).await,
+        TimerEvent::SessionBack => TimerInterface::session_back(ctxt).await,
+        TimerEvent::IdleReturned(away) => TimerInterface::idle_returned(ctxt, away.as_secs()).await,
+        TimerEvent::InhibitorsChanged => TimerInterface::inhibitors_changed(ctxt).await,
+        TimerEvent::RuleChanged(rule) => {
+            TimerInterface::rule_changed(ctxt, rule.as_deref().unwrap_or_default()).await
+        }
     }
 }
 
-/// Start D-Bus service
-pub async fn start_service(timer: Arc<TimerService>) -> zbus::Result<zbus::Connection> {
-    let interface = TimerInterface::new(timer);
+/// Convert a status entry into an owned D-Bus variant
+fn owned<'a, T: Into<Value<'a>>>(value: T) -> Result<OwnedValue, Error> {
+    OwnedValue::try_from(value.into()).map_err(|e| Error::ZBus(zbus::Error::Variant(e)))
+}
+
+/// Start D-Bus service on the shared session bus connection
+pub async fn start_service(timer: Arc<TimerService>, connection: &Connection) -> zbus::Result<()> {
+    let mut events = timer.subscribe();
+    let interface = TimerInterface::new(timer.clone());
+
+    connection.object_server().at(OBJECT_PATH, interface).await?;
+    connection.request_name(SERVICE_NAME).await?;
+
+    // Inhibitors go away with the application holding them, also if it
+    // crashes
+    let bus = zbus::fdo::DBusProxy::new(connection).await?;
+    let mut owner_changes = bus.receive_name_owner_changed().await?;
+    tokio::spawn(async move {
+        while let Some(change) = owner_changes.next().await {
+            let Ok(args) = change.args() else {
+                continue;
+            };
+            if args.new_owner().is_none() {
+                timer.release_inhibitors(args.name().as_str()).await;
+            }
+        }
+    });
+
+    // Mirror timer events as D-Bus signals
+    let ctxt = SignalContext::new(connection, OBJECT_PATH)?.into_owned();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if let Err(e) = emit(&ctxt, &event).await {
+                eprintln!("Failed to emit D-Bus signal: {}", e);
+            }
+        }
+    });
 
-    ConnectionBuilder::session()?
-        .name(SERVICE_NAME)?
-        .serve_at(OBJECT_PATH, interface)?
-        .build()
-        .await
+    Ok(())
 }
@@ -0,0 +1,85 @@
+//! Log of finished breaks
+//!
+//! Every break that ended, was skipped or happened naturally while the
+//! user was away is appended to `history.ron` in the state directory, one
+//! record per line.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::clock::Clock;
+use crate::state::state_dir;
+use crate::timer::TimerEvent;
+
+/// One finished break
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakRecord {
+    /// Break kind
+    pub name: String,
+
+    /// When the break finished
+    pub at: DateTime<Local>,
+
+    /// How the break went
+    pub outcome: Outcome,
+}
+
+/// How a break went
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Outcome {
+    /// Ran its full length
+    Taken,
+    /// Cut short
+    Skipped,
+    /// The user was away at least as long as the break, for `idle` seconds
+    Natural { idle: u64 },
+}
+
+impl BreakRecord {
+    /// Get history file path
+    pub fn history_path() -> PathBuf {
+        state_dir().join("history.ron")
+    }
+
+    /// Append this record to the history file
+    pub fn append(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let line = ron::to_string(self)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::history_path())?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
+
+/// Record finished breaks from timer events
+///
+/// Records are stamped with the timer's `clock`. Runs until the timer
+/// service goes away.
+pub async fn watch_events(mut events: broadcast::Receiver<TimerEvent>, clock: Arc<dyn Clock>) {
+    loop {
+        let (name, outcome) = match events.recv().await {
+            Ok(TimerEvent::BreakEnded(name)) => (name, Outcome::Taken),
+            Ok(TimerEvent::Skipped(name)) => (name, Outcome::Skipped),
+            Ok(TimerEvent::NaturalBreak { name, idle }) => {
+                (name, Outcome::Natural { idle: idle.as_secs() })
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+
+        let record = BreakRecord {
+            name,
+            at: clock.now(),
+            outcome,
+        };
+        if let Err(e) = record.append() {
+            eprintln!("Failed to record break history: {}", e);
+        }
+    }
+}
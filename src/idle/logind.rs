@@ -0,0 +1,78 @@
+//! Idle time from the `IdleHint` of the logind session
+//!
+//! The desktop sets the hint only after its own idle timeout, usually a
+//! few minutes, so shorter idle times are unknown. Input during breaks and
+//! idle gaps shorter than that go unseen with this backend.
+
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zbus::Connection;
+
+use super::{IdleBackend, Result};
+use crate::logind::SessionProxy;
+
+pub struct LogindBackend {
+    session: SessionProxy<'static>,
+}
+
+impl LogindBackend {
+    /// Connect to the caller's session
+    ///
+    /// Every session has the hint, but only some desktops keep it up to
+    /// date. A session that never went idle most likely has nobody
+    /// maintaining it, so it doesn't count as working.
+    pub async fn connect(connection: &Connection) -> Result<Self> {
+        let session = SessionProxy::new(connection).await?;
+        if !session.idle_hint().await? && session.idle_since_hint().await? == 0 {
+            return Err("session idle hint is not maintained".into());
+        }
+        Ok(Self { session })
+    }
+
+    /// Wait until the hint flips to `idle`
+    async fn hint(&self, idle: bool) -> Result<()> {
+        let mut changes = self.session.receive_idle_hint_changed().await;
+        if self.session.idle_hint().await? == idle {
+            return Ok(());
+        }
+        while let Some(change) = changes.next().await {
+            if change.get().await? == idle {
+                return Ok(());
+            }
+        }
+        Err("logind went away".into())
+    }
+}
+
+impl IdleBackend for LogindBackend {
+    fn name(&self) -> &'static str {
+        "logind IdleHint"
+    }
+
+    fn idle_time(&self) -> BoxFuture<'_, Result<Option<Duration>>> {
+        Box::pin(async move {
+            // Below the desktop's own idle timeout, only recent activity is
+            // known
+            if !self.session.idle_hint().await? {
+                return Ok(None);
+            }
+            let since = UNIX_EPOCH + Duration::from_micros(self.session.idle_since_hint().await?);
+            Ok(Some(SystemTime::now().duration_since(since).unwrap_or_default()))
+        })
+    }
+
+    fn wait_idle(&self, _threshold: Duration) -> Option<BoxFuture<'_, Result<()>>> {
+        // The idle time is unknown until the desktop sets the hint, so that
+        // is the only change worth waiting for. Once idle, the caller sleeps
+        // until the threshold instead.
+        match self.session.cached_idle_hint() {
+            Ok(Some(false)) => Some(Box::pin(self.hint(true))),
+            _ => None,
+        }
+    }
+
+    fn wait_active(&self) -> Option<BoxFuture<'_, Result<()>>> {
+        Some(Box::pin(self.hint(false)))
+    }
+}
@@ -0,0 +1,279 @@
+//! Idle detection
+//!
+//! Desktops expose idle time in different ways, so several backends are
+//! probed at startup and the first one that works is used. Backends share
+//! the applet's session and system bus connections, and those that signal
+//! idle and activity changes are waited on instead of polled.
+
+mod logind;
+mod mutter;
+mod screensaver;
+mod wayland;
+
+use crate::config::BreakActivity;
+use crate::timer::{IdleStatus, TimerService, TimerState, BREAK_ACTIVITY_WINDOW};
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use zbus::Connection;
+
+/// How often to look for activity again once the user is away
+const AWAY_POLL: Duration = Duration::from_secs(2);
+
+/// How often to sample input during a break that requires the user to be
+/// away
+const BREAK_POLL: Duration = Duration::from_secs(1);
+
+/// Jitter allowed between idle time samples of one stretch
+const STRETCH_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// How long to wait when idle detection is off or unavailable
+const RETRY: Duration = Duration::from_secs(60);
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// A source of the session's idle time
+pub trait IdleBackend: Send + Sync {
+    /// Name shown in status output
+    fn name(&self) -> &'static str;
+
+    /// How long the user hasn't touched keyboard or mouse
+    ///
+    /// `None` if the backend can only tell that the user was active
+    /// recently, not how long ago exactly.
+    fn idle_time(&self) -> BoxFuture<'_, Result<Option<Duration>>>;
+
+    /// Resolve once the idle time reaches `threshold`, or possibly earlier
+    ///
+    /// `None` if the backend can't signal this and has to be polled.
+    fn wait_idle(&self, _threshold: Duration) -> Option<BoxFuture<'_, Result<()>>> {
+        None
+    }
+
+    /// Resolve once the user is active again
+    ///
+    /// `None` if the backend can't signal this and has to be polled.
+    fn wait_active(&self) -> Option<BoxFuture<'_, Result<()>>> {
+        None
+    }
+}
+
+/// Find the first idle backend that works on this desktop
+///
+/// Tried in order: `org.freedesktop.ScreenSaver`,
+/// `org.gnome.Mutter.IdleMonitor`, the `IdleHint` of the logind session
+/// and the Wayland `ext-idle-notify-v1` protocol. The first two need the
+/// session bus, logind the system bus.
+pub async fn probe(
+    session: Option<&Connection>,
+    system: Option<&Connection>,
+) -> Option<Box<dyn IdleBackend>> {
+    let candidates: [BoxFuture<'_, Result<Box<dyn IdleBackend>>>; 4] = [
+        Box::pin(async {
+            let session = session.ok_or("no session bus")?;
+            let backend = screensaver::ScreenSaverBackend::connect(session).await?;
+            Ok(Box::new(backend) as Box<dyn IdleBackend>)
+        }),
+        Box::pin(async {
+            let session = session.ok_or("no session bus")?;
+            let backend = mutter::MutterBackend::connect(session).await?;
+            Ok(Box::new(backend) as Box<dyn IdleBackend>)
+        }),
+        Box::pin(async {
+            let system = system.ok_or("no system bus")?;
+            let backend = logind::LogindBackend::connect(system).await?;
+            Ok(Box::new(backend) as Box<dyn IdleBackend>)
+        }),
+        Box::pin(async {
+            let backend = wayland::WaylandBackend::connect().await?;
+            Ok(Box::new(backend) as Box<dyn IdleBackend>)
+        }),
+    ];
+
+    for candidate in candidates {
+        if let Ok(backend) = candidate.await {
+            return Some(backend);
+        }
+    }
+    None
+}
+
+/// Pause the timer while the user is away and resume it on return
+///
+/// Idle stretches at least as long as a break are reported when they end,
+/// so they count as natural breaks. Also feeds active-time accounting when
+/// `active_time_only` is set, and holds up breaks the user keeps working
+/// through unless `break_activity` is `Ignore`.
+///
+/// Waits for the backend to signal that the next idle threshold was
+/// reached, or that the user is back once one was. Backends that can't
+/// signal are polled: the watcher sleeps until the next threshold could be
+/// reached at the earliest, so a user idle for 1 minute with a 5 minute
+/// threshold is checked again in 4 minutes, and only while away is
+/// activity polled for frequently. Timer events, e.g. a config change,
+/// trigger an early re-check.
+///
+/// The backend is probed once idle detection is first needed, and again
+/// after it stops working.
+pub async fn watch(
+    timer: Arc<TimerService>,
+    session: Option<Connection>,
+    system: Option<Connection>,
+) {
+    let mut events = timer.subscribe();
+    let mut backend: Option<Box<dyn IdleBackend>> = None;
+    // Whether the backend was found unable to see short idle times
+    let mut coarse = false;
+    // Start of the current idle stretch, once it is long enough to matter
+    let mut idle_since: Option<Instant> = None;
+
+    loop {
+        let config = timer.config().await;
+        let shortest_break = config.enabled_breaks().map(|b| b.duration).min();
+        let natural = shortest_break
+            .filter(|_| config.idle_detection)
+            .map(Duration::from_secs);
+        let mut thresholds: Vec<Duration> = [
+            config
+                .idle_detection
+                .then_some(Duration::from_secs(config.idle_threshold)),
+            config
+                .active_time_only
+                .then_some(Duration::from_secs(config.idle_gap)),
+            natural,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let state = timer.state().await;
+        let enforced = matches!(state, TimerState::InBreak(_))
+            && config.break_activity != BreakActivity::Ignore;
+        // Input during an enforced break holds it up until the user stops
+        if enforced {
+            thresholds.push(BREAK_ACTIVITY_WINDOW);
+        }
+
+        // Without a working backend, assume not idle. This prevents false
+        // pauses, and the status output tells why nothing happens
+        let sample = if thresholds.is_empty() {
+            timer.set_idle_status(IdleStatus::Unused).await;
+            None
+        } else {
+            if backend.is_none() {
+                backend = probe(session.as_ref(), system.as_ref()).await;
+                coarse = false;
+                let status = match &backend {
+                    Some(backend) => IdleStatus::Backend(backend.name()),
+                    None => IdleStatus::Unavailable,
+                };
+                if timer.set_idle_status(status).await {
+                    match status {
+                        IdleStatus::Backend(name) => eprintln!("Idle detection: using {}", name),
+                        _ => eprintln!("Idle detection: no backend works on this desktop"),
+                    }
+                }
+            }
+            match &backend {
+                Some(current) => match current.idle_time().await {
+                    Ok(idle) => Some(idle),
+                    Err(e) => {
+                        eprintln!("Idle detection via {} failed: {}", current.name(), e);
+                        backend = None;
+                        None
+                    }
+                },
+                None => None,
+            }
+        };
+
+        // Recent activity is as good as no idle time for pausing and idle
+        // stretches, but says nothing about input during a break
+        let idle = sample.map(Option::unwrap_or_default);
+        match sample {
+            Some(Some(idle)) => timer.record_idle(idle).await,
+            Some(None) => {
+                timer.record_active().await;
+                if !coarse && (enforced || config.active_time_only) {
+                    coarse = true;
+                    if let Some(current) = &backend {
+                        eprintln!(
+                            "Idle detection via {} can't see short idle times; \
+                             input during breaks and short idle gaps go unnoticed",
+                            current.name()
+                        );
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if let Some(idle) = idle {
+            // Input since the stretch started means the user came back
+            let now = Instant::now();
+            let started = now.checked_sub(idle).unwrap_or(now);
+            if let Some(since) = idle_since {
+                if started > since + STRETCH_TOLERANCE {
+                    timer.idle_ended(started - since).await;
+                    idle_since = None;
+                }
+            }
+            if natural.is_some_and(|natural| idle >= natural) {
+                idle_since.get_or_insert(started);
+            }
+
+            if config.idle_detection {
+                let away = idle >= Duration::from_secs(config.idle_threshold);
+                // Being idle during a break is the point, so breaks are
+                // left alone. Pauses the user asked for are theirs to end
+                if away && state.fires_breaks() {
+                    let _ = timer.pause_while_idle(idle).await;
+                } else if !away && state == TimerState::Paused {
+                    let _ = timer.resume_from_idle().await;
+                }
+            }
+        }
+
+        // Wait for the next threshold, or for the user to come back once
+        // one was reached. Only what the backend can't signal is polled
+        let next = idle.and_then(|idle| thresholds.iter().filter(|t| **t > idle).min().copied());
+        let away = idle.is_some_and(|idle| thresholds.iter().any(|t| idle >= *t));
+        let idle_signal = next.and_then(|threshold| backend.as_ref()?.wait_idle(threshold));
+        let active_signal = backend
+            .as_ref()
+            .filter(|_| away)
+            .and_then(|backend| backend.wait_active());
+
+        let delay = match idle {
+            Some(idle) => {
+                let poll = if enforced { BREAK_POLL } else { AWAY_POLL };
+                [
+                    (away && active_signal.is_none()).then_some(poll),
+                    next.filter(|_| idle_signal.is_none()).map(|next| next - idle),
+                ]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(RETRY)
+            }
+            None => RETRY,
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = events.recv() => {}
+            _ = signalled(idle_signal) => {}
+            _ = signalled(active_signal) => {}
+        }
+    }
+}
+
+/// Resolve when a backend signal does; never without one or if it fails
+async fn signalled(signal: Option<BoxFuture<'_, Result<()>>>) {
+    if let Some(signal) = signal {
+        match signal.await {
+            Ok(()) => return,
+            Err(e) => eprintln!("Idle detection signal failed: {}", e),
+        }
+    }
+    std::future::pending().await
+}
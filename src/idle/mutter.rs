@@ -0,0 +1,104 @@
+//! Idle time from GNOME Shell's `org.gnome.Mutter.IdleMonitor`
+//!
+//! Besides reporting the idle time, the monitor fires watches when the
+//! user reaches an idle time or becomes active again.
+
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use zbus::{proxy, Connection};
+
+use super::{IdleBackend, Result};
+
+/// D-Bus proxy for the core (user activity) idle monitor
+#[proxy(
+    interface = "org.gnome.Mutter.IdleMonitor",
+    default_service = "org.gnome.Mutter.IdleMonitor",
+    default_path = "/org/gnome/Mutter/IdleMonitor/Core"
+)]
+trait IdleMonitor {
+    /// Get idle time in milliseconds
+    async fn get_idletime(&self) -> zbus::Result<u64>;
+
+    /// Fire a watch each time the idle time reaches `interval` milliseconds
+    async fn add_idle_watch(&self, interval: u64) -> zbus::Result<u32>;
+
+    /// Fire a watch once, the next time the user is active
+    async fn add_user_active_watch(&self) -> zbus::Result<u32>;
+
+    /// The watch with this id fired
+    #[zbus(signal)]
+    fn watch_fired(&self, id: u32) -> zbus::Result<()>;
+}
+
+pub struct MutterBackend {
+    proxy: IdleMonitorProxy<'static>,
+    /// Idle watches by interval in milliseconds
+    ///
+    /// They keep firing on every idle stretch, so each one is added once
+    /// and reused.
+    idle_watches: Mutex<HashMap<u64, u32>>,
+}
+
+impl MutterBackend {
+    /// Connect, failing unless the idle monitor answers
+    pub async fn connect(connection: &Connection) -> Result<Self> {
+        let backend = Self {
+            proxy: IdleMonitorProxy::new(connection).await?,
+            idle_watches: Mutex::new(HashMap::new()),
+        };
+        backend.idle_time().await?;
+        Ok(backend)
+    }
+
+    /// Wait for the watch that `add` sets up to fire
+    ///
+    /// Subscribes first, so a watch firing right away isn't missed.
+    async fn fired<F>(&self, add: F) -> Result<()>
+    where
+        F: std::future::Future<Output = zbus::Result<u32>>,
+    {
+        let mut fired = self.proxy.receive_watch_fired().await?;
+        let id = add.await?;
+        while let Some(signal) = fired.next().await {
+            if signal.args()?.id == id {
+                return Ok(());
+            }
+        }
+        Err("idle monitor went away".into())
+    }
+}
+
+impl IdleBackend for MutterBackend {
+    fn name(&self) -> &'static str {
+        "Mutter IdleMonitor"
+    }
+
+    fn idle_time(&self) -> BoxFuture<'_, Result<Option<Duration>>> {
+        Box::pin(async move {
+            let idle_ms = self.proxy.get_idletime().await?;
+            Ok(Some(Duration::from_millis(idle_ms)))
+        })
+    }
+
+    fn wait_idle(&self, threshold: Duration) -> Option<BoxFuture<'_, Result<()>>> {
+        let interval = threshold.as_millis() as u64;
+        Some(Box::pin(self.fired(async move {
+            let mut watches = self.idle_watches.lock().await;
+            if let Some(id) = watches.get(&interval) {
+                return Ok(*id);
+            }
+            let id = self.proxy.add_idle_watch(interval).await?;
+            watches.insert(interval, id);
+            Ok(id)
+        })))
+    }
+
+    fn wait_active(&self) -> Option<BoxFuture<'_, Result<()>>> {
+        // Removes itself once fired; one left behind by a waiter that went
+        // away just fires at the next input
+        Some(Box::pin(self.fired(self.proxy.add_user_active_watch())))
+    }
+}
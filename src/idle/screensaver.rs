@@ -0,0 +1,52 @@
+//! Idle time from the `org.freedesktop.ScreenSaver` interface (KDE and
+//! others)
+//!
+//! The interface has no idle or activity signals, so this backend is
+//! polled.
+
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use zbus::{proxy, Connection};
+
+use super::{IdleBackend, Result};
+
+/// D-Bus proxy for ScreenSaver interface (KDE/GNOME compatible)
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    /// Get idle time in milliseconds
+    async fn get_session_idle_time(&self) -> zbus::Result<u32>;
+}
+
+pub struct ScreenSaverBackend {
+    proxy: ScreenSaverProxy<'static>,
+}
+
+impl ScreenSaverBackend {
+    /// Connect, failing unless the service actually answers
+    ///
+    /// Some desktops own the name but reply `NotSupported`.
+    pub async fn connect(connection: &Connection) -> Result<Self> {
+        let backend = Self {
+            proxy: ScreenSaverProxy::new(connection).await?,
+        };
+        backend.idle_time().await?;
+        Ok(backend)
+    }
+}
+
+impl IdleBackend for ScreenSaverBackend {
+    fn name(&self) -> &'static str {
+        "ScreenSaver"
+    }
+
+    fn idle_time(&self) -> BoxFuture<'_, Result<Option<Duration>>> {
+        Box::pin(async move {
+            let idle_ms = self.proxy.get_session_idle_time().await?;
+            Ok(Some(Duration::from_millis(idle_ms as u64)))
+        })
+    }
+}
@@ -0,0 +1,208 @@
+//! Idle time from the Wayland `ext-idle-notify-v1` protocol
+//!
+//! The compositor only tells when the user went idle and when they came
+//! back, after a timeout set up front. A short-timeout notification tracks
+//! the idle time; thresholds get a notification of their own.
+
+use futures_util::future::BoxFuture;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch};
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{Connection, Dispatch, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+};
+
+use super::{IdleBackend, Result};
+
+/// Input-free time after which the compositor reports the user as idle
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Error once the dispatch thread is gone
+const DISCONNECTED: &str = "lost connection to the Wayland compositor";
+
+pub struct WaylandBackend {
+    connection: Connection,
+    qh: QueueHandle<State>,
+    seat: wl_seat::WlSeat,
+    notifier: ExtIdleNotifierV1,
+    /// When the user went idle, while they are
+    idle_since: watch::Receiver<Option<Instant>>,
+}
+
+/// Event queue state, updated on the dispatch thread
+struct State {
+    idle_since: watch::Sender<Option<Instant>>,
+}
+
+/// Notification for one threshold, resolving the waiter once it fires
+struct Waiter(Mutex<Option<oneshot::Sender<()>>>);
+
+/// Withdraws a threshold notification when its waiter goes away
+struct Withdraw(ExtIdleNotificationV1);
+
+impl Drop for Withdraw {
+    fn drop(&mut self) {
+        self.0.destroy();
+    }
+}
+
+impl WaylandBackend {
+    /// Connect to the compositor and subscribe to idle notifications
+    ///
+    /// Fails outside a Wayland session or if the compositor lacks the
+    /// protocol. Events are dispatched on a thread of their own.
+    pub async fn connect() -> Result<Self> {
+        let connection = Connection::connect_to_env()?;
+        let (globals, mut queue) = registry_queue_init::<State>(&connection)?;
+        let qh = queue.handle();
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ())?;
+        let notifier: ExtIdleNotifierV1 = globals.bind(&qh, 1..=1, ())?;
+        notifier.get_idle_notification(TIMEOUT.as_millis() as u32, &seat, &qh, ());
+
+        let (sender, idle_since) = watch::channel(None);
+        let mut state = State { idle_since: sender };
+        queue.roundtrip(&mut state)?;
+        std::thread::spawn(move || {
+            while queue.blocking_dispatch(&mut state).is_ok() {}
+            eprintln!("{}", DISCONNECTED);
+        });
+
+        Ok(Self {
+            connection,
+            qh,
+            seat,
+            notifier,
+            idle_since,
+        })
+    }
+
+    fn current_idle(&self) -> Duration {
+        self.idle_since
+            .borrow()
+            .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+impl IdleBackend for WaylandBackend {
+    fn name(&self) -> &'static str {
+        "Wayland ext-idle-notify"
+    }
+
+    fn idle_time(&self) -> BoxFuture<'_, Result<Option<Duration>>> {
+        Box::pin(async move {
+            self.idle_since.has_changed().map_err(|_| DISCONNECTED)?;
+            Ok(Some(self.current_idle()))
+        })
+    }
+
+    fn wait_idle(&self, threshold: Duration) -> Option<BoxFuture<'_, Result<()>>> {
+        // The compositor counts from now, not from the last input
+        let remaining = threshold.saturating_sub(self.current_idle());
+        let timeout = remaining.as_millis().clamp(1, u32::MAX as u128) as u32;
+        let (sender, fired) = oneshot::channel();
+        let waiter = Waiter(Mutex::new(Some(sender)));
+        let notification = Withdraw(self.notifier.get_idle_notification(
+            timeout,
+            &self.seat,
+            &self.qh,
+            waiter,
+        ));
+
+        Some(Box::pin(async move {
+            let _notification = notification;
+            self.connection.flush()?;
+            fired.await.map_err(|_| DISCONNECTED)?;
+            Ok(())
+        }))
+    }
+
+    fn wait_active(&self) -> Option<BoxFuture<'_, Result<()>>> {
+        let mut idle_since = self.idle_since.clone();
+        Some(Box::pin(async move {
+            idle_since
+                .wait_for(|since| since.is_none())
+                .await
+                .map_err(|_| DISCONNECTED)?;
+            Ok(())
+        }))
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => {
+                let now = Instant::now();
+                state.idle_since.send_replace(Some(now.checked_sub(TIMEOUT).unwrap_or(now)));
+            }
+            ext_idle_notification_v1::Event::Resumed => {
+                state.idle_since.send_replace(None);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, Waiter> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        waiter: &Waiter,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_idle_notification_v1::Event::Idled = event {
+            if let Some(sender) = waiter.0.lock().unwrap().take() {
+                let _ = sender.send(());
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotifierV1,
+        _: ext_idle_notifier_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
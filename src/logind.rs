@@ -0,0 +1,238 @@
+//! Session and power integration via systemd-logind
+//!
+//! Talks to `org.freedesktop.login1` on the system bus. The tests start a
+//! private dbus-daemon and stand in for logind on it.
+
+use futures_util::StreamExt;
+use std::sync::Arc;
+use zbus::{proxy, Connection};
+
+use crate::timer::TimerService;
+
+/// D-Bus proxy for the logind manager
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    /// Emitted with `true` right before suspend and `false` after resume
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// D-Bus proxy for the logind session this process belongs to
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+pub trait Session {
+    /// Whether the session is idle
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+
+    /// When the session went idle, in microseconds since the Unix epoch
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> zbus::Result<u64>;
+
+    /// Whether the screen locker reports the session as locked
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+
+    /// Whether the session is in the foreground of its seat
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    /// Emitted when the session is asked to lock its screen
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    /// Emitted when the session is asked to unlock its screen
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+}
+
+/// Forward suspend and resume notifications to the timer service
+///
+/// Runs until the signal stream ends.
+pub async fn watch_sleep(timer: Arc<TimerService>, connection: Connection) -> zbus::Result<()> {
+    let manager = ManagerProxy::new(&connection).await?;
+    let mut signals = manager.receive_prepare_for_sleep().await?;
+
+    while let Some(signal) = signals.next().await {
+        if signal.args()?.start {
+            timer.prepare_for_sleep().await;
+        } else {
+            timer.wake_from_sleep().await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Tell the timer service when the session is locked or switched away from
+///
+/// Follows `LockedHint` and `Active`, and also the `Lock` and `Unlock`
+/// requests, since not every screen locker keeps the hint up to date.
+/// Runs until the session goes away.
+pub async fn watch_session(timer: Arc<TimerService>, connection: Connection) -> zbus::Result<()> {
+    let session = SessionProxy::new(&connection).await?;
+    let mut locked_changes = session.receive_locked_hint_changed().await;
+    let mut active_changes = session.receive_active_changed().await;
+    let mut locks = session.receive_lock().await?;
+    let mut unlocks = session.receive_unlock().await?;
+
+    let mut locked = session.locked_hint().await?;
+    let mut active = session.active().await?;
+    loop {
+        if locked || !active {
+            timer.session_away().await;
+        } else {
+            timer.session_back().await;
+        }
+
+        tokio::select! {
+            Some(change) = locked_changes.next() => locked = change.get().await?,
+            Some(change) = active_changes.next() => active = change.get().await?,
+            Some(_) = locks.next() => locked = true,
+            Some(_) = unlocks.next() => locked = false,
+            else => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::config::Config;
+    use chrono::{Duration, Local};
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    /// A private bus, shut down on drop
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Start a dbus-daemon of our own, which needs `dbus-daemon` on
+        /// the `PATH`
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("failed to start dbus-daemon");
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Self {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+
+        async fn connect(&self) -> Connection {
+            zbus::connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Time left until each break kind
+    async fn time_left(timer: &TimerService) -> Vec<(String, Duration)> {
+        timer.snapshot().await.breaks
+    }
+
+    /// Send logind's suspend or resume notification
+    async fn prepare_for_sleep(logind: &Connection, start: bool) {
+        logind
+            .emit_signal(
+                None::<()>,
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+                "PrepareForSleep",
+                &(start,),
+            )
+            .await
+            .unwrap();
+    }
+
+    /// Wait until `done` says so, for a few seconds at most
+    async fn until<F: std::future::Future<Output = bool>>(mut done: impl FnMut() -> F) {
+        let poll = async {
+            while !done().await {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), poll)
+            .await
+            .expect("the sleep watcher didn't keep up");
+    }
+
+    /// Run `watch_sleep` against a stand-in for logind that suspends the
+    /// machine for `slept`, after ten minutes of work
+    async fn suspend(slept: Duration) -> Vec<(String, Duration)> {
+        let bus = PrivateBus::start();
+        let logind = bus.connect().await;
+        logind.request_name("org.freedesktop.login1").await.unwrap();
+
+        let clock = Arc::new(ManualClock::new(Local::now()));
+        let timer = Arc::new(TimerService::with_clock(Config::default(), clock.clone()));
+        tokio::spawn(watch_sleep(timer.clone(), bus.connect().await));
+        clock.advance(Duration::minutes(10));
+
+        // Signals sent before the watcher subscribed are lost, so repeat
+        // the suspend notification until it arrives
+        until(|| async {
+            prepare_for_sleep(&logind, true).await;
+            timer.suspended().await
+        })
+        .await;
+        // Both time lines move, as with a monotonic clock that counts suspend
+        clock.advance(slept);
+        prepare_for_sleep(&logind, false).await;
+        until(|| async { !timer.suspended().await }).await;
+
+        time_left(&timer).await
+    }
+
+    #[tokio::test]
+    async fn test_short_suspend_pauses_countdowns() {
+        let left = suspend(Duration::seconds(10)).await;
+        assert_eq!(
+            left,
+            [
+                ("short".to_string(), Duration::minutes(10)),
+                ("long".to_string(), Duration::minutes(50)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_suspend_counts_as_break() {
+        let left = suspend(Duration::minutes(6)).await;
+        assert_eq!(
+            left,
+            [
+                ("short".to_string(), Duration::minutes(20)),
+                ("long".to_string(), Duration::minutes(60)),
+            ]
+        );
+    }
+}
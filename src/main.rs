@@ -1,10 +1,14 @@
 mod applet;
 mod break_screen;
+mod clock;
 mod config;
 mod timer;
 mod dbus;
+mod history;
 mod idle;
+mod logind;
 mod notify;
+mod state;
 
 use applet::CosmicEyes;
 use config::Config;
@@ -1,7 +1,12 @@
 //! Desktop notifications via D-Bus
 
+use futures_util::StreamExt;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use zbus::{proxy, Connection};
 
+use crate::timer::{TimerEvent, TimerService, RETURN_ANSWER_WINDOW};
+
 /// D-Bus proxy for Notifications interface
 #[proxy(
     interface = "org.freedesktop.Notifications",
@@ -22,32 +27,171 @@ trait Notifications {
         hints: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
         expire_timeout: i32,
     ) -> zbus::Result<u32>;
+
+    /// Emitted when the user picks an action of a notification
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    /// Emitted when a notification expired or was dismissed
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
 }
 
-/// Send a desktop notification
-pub async fn send_notification(title: &str, body: &str) {
-    if let Err(e) = try_send_notification(title, body).await {
-        // Log error but don't fail - notifications are optional
-        eprintln!("Failed to send notification: {}", e);
+/// Sends desktop notifications over a shared session bus connection
+#[derive(Clone)]
+pub struct Notifier {
+    proxy: NotificationsProxy<'static>,
+}
+
+impl Notifier {
+    pub async fn new(connection: &Connection) -> zbus::Result<Self> {
+        Ok(Self {
+            proxy: NotificationsProxy::new(connection).await?,
+        })
+    }
+
+    /// Send a desktop notification
+    pub async fn send(&self, title: &str, body: &str) {
+        if let Err(e) = self.try_send(title, body).await {
+            // Log error but don't fail - notifications are optional
+            eprintln!("Failed to send notification: {}", e);
+        }
+    }
+
+    /// Send a desktop notification offering `actions` as key and label
+    /// pairs for `expire`, and wait for the user to pick one
+    ///
+    /// `None` if the notification expired, was dismissed or couldn't be
+    /// sent.
+    pub async fn ask(
+        &self,
+        title: &str,
+        body: &str,
+        actions: &[(&str, &str)],
+        expire: std::time::Duration,
+    ) -> Option<String> {
+        match self.try_ask(title, body, actions, expire).await {
+            Ok(choice) => choice,
+            Err(e) => {
+                eprintln!("Failed to send notification: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn try_send(&self, title: &str, body: &str) -> zbus::Result<u32> {
+        self.notify(title, body, &[], 5000).await // 5 seconds
+    }
+
+    async fn try_ask(
+        &self,
+        title: &str,
+        body: &str,
+        actions: &[(&str, &str)],
+        expire: std::time::Duration,
+    ) -> zbus::Result<Option<String>> {
+        // Subscribe first so a quick answer isn't missed
+        let mut invoked = self.proxy.receive_action_invoked().await?;
+        let mut closed = self.proxy.receive_notification_closed().await?;
+        let actions: Vec<&str> = actions
+            .iter()
+            .flat_map(|(key, label)| [*key, *label])
+            .collect();
+        let expire_timeout = i32::try_from(expire.as_millis()).unwrap_or(i32::MAX);
+        let id = self.notify(title, body, &actions, expire_timeout).await?;
+
+        let answer = async {
+            loop {
+                tokio::select! {
+                    Some(signal) = invoked.next() => {
+                        let args = signal.args()?;
+                        if args.id == id {
+                            return Ok(Some(args.action_key));
+                        }
+                    }
+                    Some(signal) = closed.next() => {
+                        if signal.args()?.id == id {
+                            return Ok(None);
+                        }
+                    }
+                    else => return Ok(None),
+                }
+            }
+        };
+        // Not every notification server closes expired notifications
+        tokio::time::timeout(expire, answer)
+            .await
+            .unwrap_or(Ok(None))
+    }
+
+    async fn notify(
+        &self,
+        title: &str,
+        body: &str,
+        actions: &[&str],
+        expire_timeout: i32,
+    ) -> zbus::Result<u32> {
+        self.proxy
+            .notify(
+                "Cosmic Eyes",
+                0,
+                "cosmic-eyes",
+                title,
+                body,
+                actions,
+                std::collections::HashMap::new(),
+                expire_timeout,
+            )
+            .await
     }
 }
 
-async fn try_send_notification(title: &str, body: &str) -> zbus::Result<()> {
-    let connection = Connection::session().await?;
-    let proxy = NotificationsProxy::new(&connection).await?;
-
-    proxy
-        .notify(
-            "Cosmic Eyes",
-            0,
-            "cosmic-eyes",
-            title,
-            body,
-            &[],
-            std::collections::HashMap::new(),
-            5000, // 5 seconds
-        )
-        .await?;
-
-    Ok(())
+/// Turn timer events into desktop notifications
+///
+/// Answers to notifications that offer a choice go back to `timer`. Runs
+/// until the timer service goes away.
+pub async fn watch_events(
+    mut events: broadcast::Receiver<TimerEvent>,
+    notifier: Notifier,
+    timer: Arc<TimerService>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(TimerEvent::PreBreakWarning { name, .. }) => {
+                let body = format!("Your {} break will start soon. Save your work!", name);
+                notifier.send("Break Soon", &body).await;
+            }
+            Ok(TimerEvent::FocusEnded(Some(catch_up))) => {
+                let body = format!(
+                    "Time to catch up on the breaks you missed: {} minutes.",
+                    (catch_up.as_secs_f64() / 60.0).ceil()
+                );
+                notifier.send("Focus Over", &body).await;
+            }
+            Ok(TimerEvent::PauseExpired) => {
+                notifier.send("Pause Over", "Break reminders are running again.").await;
+            }
+            Ok(TimerEvent::IdleReturned(away)) => {
+                let body = format!(
+                    "You were away for {} minutes. Count that as a break?",
+                    away.as_secs() / 60
+                );
+                let notifier = notifier.clone();
+                let timer = timer.clone();
+                // Waiting for the answer mustn't hold up other notifications
+                tokio::spawn(async move {
+                    let actions = [("break", "Count as break"), ("keep", "Keep timers")];
+                    let choice = notifier
+                        .ask("Welcome Back", &body, &actions, RETURN_ANSWER_WINDOW)
+                        .await;
+                    if choice.as_deref() == Some("break") {
+                        // Too late once the next break or postpone settled it
+                        let _ = timer.count_away_as_break().await;
+                    }
+                });
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+    }
 }
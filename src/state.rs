@@ -0,0 +1,99 @@
+//! Timer state persisted across restarts
+//!
+//! Deadlines are monotonic instants, which mean nothing to another process,
+//! so they are stored as wall-clock times and converted back on startup.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::timer::TimerState;
+
+/// Everything needed to pick up where the last run left off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    /// When this state was written
+    pub saved_at: DateTime<Local>,
+
+    /// Timer state at that time
+    pub state: TimerState,
+
+    /// Next deadline per scheduled break kind
+    pub next_breaks: HashMap<String, DateTime<Local>>,
+
+    /// End of the active break, if any
+    #[serde(default)]
+    pub break_end: Option<DateTime<Local>>,
+
+    /// Current work block within the Pomodoro set
+    pub pomodoro: u32,
+
+    /// Times each break kind was postponed since it was last taken
+    #[serde(default)]
+    pub postpones: HashMap<String, u32>,
+
+    /// When each break kind was last taken
+    #[serde(default)]
+    pub last_breaks: HashMap<String, DateTime<Local>>,
+
+    /// When a timed pause ends
+    #[serde(default)]
+    pub paused_until: Option<DateTime<Local>>,
+
+    /// Whether the pause is only because the user was away
+    #[serde(default)]
+    pub idle_paused: bool,
+
+    /// When the focus block ends
+    #[serde(default)]
+    pub focus_until: Option<DateTime<Local>>,
+
+    /// Longest break kind missed during the focus block and the total
+    /// length of all missed breaks in seconds
+    #[serde(default)]
+    pub break_debt: Option<(String, u64)>,
+}
+
+/// Directory for state kept between runs, e.g. `~/.local/state/cosmic-eyes`
+pub fn state_dir() -> PathBuf {
+    let state_dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("cosmic-eyes");
+
+    std::fs::create_dir_all(&state_dir).ok();
+    state_dir
+}
+
+impl SavedState {
+    /// Get state file path
+    pub fn state_path() -> PathBuf {
+        state_dir().join("state.ron")
+    }
+
+    /// Load the saved state, if there is a readable one
+    pub fn load() -> Option<Self> {
+        let contents = std::fs::read_to_string(Self::state_path()).ok()?;
+        match ron::from_str(&contents) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                eprintln!("Ignoring unreadable timer state: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Save state to file
+    ///
+    /// Writes a temporary file and renames it into place, so a crash
+    /// mid-write never leaves a truncated state behind.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::state_path();
+        let tmp = path.with_extension("ron.tmp");
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
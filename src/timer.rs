@@ -1,178 +1,2345 @@
 use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Instant;
+use tokio::sync::{broadcast, Notify, RwLock};
 
-use crate::config::{BreakConfig, Config};
-
-/// Type of break
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum BreakType {
-    Short,
-    Long,
-}
+use crate::clock::{Clock, SystemClock};
+use crate::config::{BreakActivity, BreakConfig, Config, IdleReturn, ScheduleMode};
+use crate::state::SavedState;
 
 /// Current state of the timer
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TimerState {
     /// Timer is running normally
     Running,
     /// Timer is paused (e.g., due to idle detection)
     Paused,
-    /// A break is active
-    InBreak(BreakType),
-    /// Break was postponed
-    Postponed,
+    /// A break of the named kind is active
+    InBreak(String),
+    /// The named break was postponed; timers keep running and the next due
+    /// break starts as usual
+    Postponed(String),
+    /// Outside the configured working hours; nothing is counted
+    OffHours,
+    /// Breaks are held back for a focus block and pile up as break debt
+    Focus,
+}
+
+/// Inputs that move the timer between states
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerAction {
+    /// Start a break of the named kind
+    StartBreak(String),
+    /// The active break ran its full length
+    EndBreak,
+    /// The active break was cut short
+    Skip,
+    /// Push back the named break kind
+    Postpone(String),
+    /// Stop firing breaks
+    Pause,
+    /// Start firing breaks again
+    Resume,
+    /// A working window ended
+    ClockOut,
+    /// A working window started
+    ClockIn,
+    /// Hold back breaks for a focus block
+    StartFocus,
+    /// A focus block is over
+    EndFocus,
+}
+
+/// Something that happened in the timer, published to every subscriber
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerEvent {
+    /// The deadline of the named break passed
+    BreakDue(String),
+    /// A break started and will last `duration`
+    BreakStarted {
+        name: String,
+        duration: std::time::Duration,
+    },
+    /// A break ran its full length
+    BreakEnded(String),
+    /// A break was cut short
+    Skipped(String),
+    /// A break was pushed back by `by`
+    Postponed {
+        name: String,
+        by: std::time::Duration,
+    },
+    /// Breaks stopped firing
+    Paused,
+    /// Breaks fire again
+    Resumed,
+    /// A new configuration was applied
+    ConfigChanged,
+    /// A break starts in `remaining`
+    PreBreakWarning {
+        name: String,
+        remaining: std::time::Duration,
+    },
+    /// Working hours ended; the timer is dormant
+    ClockedOut,
+    /// Working hours started with fresh countdowns
+    ClockedIn,
+    /// A different time-of-day override rule applies now, if any
+    RuleChanged(Option<String>),
+    /// The user was away for `idle`, long enough to count as the named
+    /// break
+    NaturalBreak {
+        name: String,
+        idle: std::time::Duration,
+    },
+    /// A timed pause ran out and the timer resumed
+    PauseExpired,
+    /// A focus block started and lasts until the given time, or until it
+    /// is ended
+    FocusStarted(Option<DateTime<Local>>),
+    /// A focus block ended, with the length of the catch-up break that
+    /// repays its break debt, if any
+    FocusEnded(Option<std::time::Duration>),
+    /// Input during the named break held up its countdown
+    BreakInterrupted(String),
+    /// The user stepped away again and the break countdown goes on
+    BreakContinued(String),
+    /// The session was locked or switched away from
+    SessionAway,
+    /// The session is unlocked and active again
+    SessionBack,
+    /// The user came back after being away this long, which could count
+    /// as a break; their countdowns stood still meanwhile
+    IdleReturned(std::time::Duration),
+    /// An application started or stopped holding back breaks
+    InhibitorsChanged,
+}
+
+impl TimerState {
+    /// The transition table
+    ///
+    /// Returns the state `action` leads to, or an error if the action is
+    /// not allowed from this state.
+    pub fn apply(&self, action: &TimerAction) -> Result<TimerState, TimerError> {
+        use TimerAction as A;
+        use TimerState as S;
+
+        match (self, action) {
+            (S::Running | S::Paused | S::Postponed(_) | S::OffHours, A::StartBreak(name)) => {
+                Ok(S::InBreak(name.clone()))
+            }
+            (S::InBreak(_), A::EndBreak | A::Skip) => Ok(S::Running),
+            (S::InBreak(active), A::Postpone(name)) if active == name => {
+                Ok(S::Postponed(name.clone()))
+            }
+            (S::Running | S::Postponed(_), A::Postpone(name)) => Ok(S::Postponed(name.clone())),
+            (S::Running | S::Postponed(_), A::Pause) => Ok(S::Paused),
+            (S::Paused, A::Resume) => Ok(S::Running),
+            (S::Running | S::Paused | S::Postponed(_), A::ClockOut) => Ok(S::OffHours),
+            (S::OffHours, A::ClockIn) => Ok(S::Running),
+            (S::Running | S::Paused | S::Postponed(_), A::StartFocus) => Ok(S::Focus),
+            (S::Focus, A::EndFocus) => Ok(S::Running),
+            (S::Focus, A::ClockOut) => Ok(S::OffHours),
+            _ => Err(TimerError::InvalidTransition {
+                from: self.clone(),
+                action: action.clone(),
+            }),
+        }
+    }
+
+    /// Whether due breaks may start from this state
+    pub fn fires_breaks(&self) -> bool {
+        matches!(self, TimerState::Running | TimerState::Postponed(_))
+    }
+}
+
+impl std::fmt::Display for TimerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerState::Running => write!(f, "running"),
+            TimerState::Paused => write!(f, "paused"),
+            TimerState::InBreak(name) => write!(f, "in {} break", name),
+            TimerState::Postponed(name) => write!(f, "postponing {} break", name),
+            TimerState::OffHours => write!(f, "outside working hours"),
+            TimerState::Focus => write!(f, "in focus mode"),
+        }
+    }
+}
+
+impl std::fmt::Display for TimerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerAction::StartBreak(name) => write!(f, "start {} break", name),
+            TimerAction::EndBreak => write!(f, "end break"),
+            TimerAction::Skip => write!(f, "skip break"),
+            TimerAction::Postpone(name) => write!(f, "postpone {} break", name),
+            TimerAction::Pause => write!(f, "pause"),
+            TimerAction::Resume => write!(f, "resume"),
+            TimerAction::ClockOut => write!(f, "clock out"),
+            TimerAction::ClockIn => write!(f, "clock in"),
+            TimerAction::StartFocus => write!(f, "start focus mode"),
+            TimerAction::EndFocus => write!(f, "end focus mode"),
+        }
+    }
+}
+
+/// Errors returned by timer operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerError {
+    /// No break kind with this name is configured
+    UnknownBreak(String),
+    /// The break kind exists but has no upcoming deadline
+    NotScheduled(String),
+    /// The action is not allowed in the current state
+    InvalidTransition {
+        from: TimerState,
+        action: TimerAction,
+    },
+    /// The break kind used up its postpones and can't be skipped or
+    /// postponed any more
+    PostponeLimit(String),
+    /// No inhibitor holds this cookie
+    UnknownInhibitor(u32),
+    /// No return from being away is waiting for an answer
+    NoPendingReturn,
+}
+
+impl std::fmt::Display for TimerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimerError::UnknownBreak(name) => write!(f, "Unknown break kind: {}", name),
+            TimerError::NotScheduled(name) => write!(f, "Break kind is not scheduled: {}", name),
+            TimerError::InvalidTransition { from, action } => {
+                write!(f, "Cannot {} while {}", action, from)
+            }
+            TimerError::PostponeLimit(name) => {
+                write!(f, "The {} break was postponed too often and must be taken", name)
+            }
+            TimerError::UnknownInhibitor(cookie) => write!(f, "No inhibitor with cookie {}", cookie),
+            TimerError::NoPendingReturn => write!(f, "No time away is waiting to count as a break"),
+        }
+    }
+}
+
+impl std::error::Error for TimerError {}
+
+/// Consistent view of the timer at one instant
+///
+/// Taken under a single lock, so the state, countdowns and cycle position
+/// always belong together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerSnapshot {
+    /// Timer state
+    pub state: TimerState,
+    /// Time until each scheduled break kind, in configuration order
+    pub breaks: Vec<(String, Duration)>,
+    /// Next break and the wall-clock time it is expected at (display only)
+    pub next_break_at: Option<(String, DateTime<Local>)>,
+    /// Pomodoro position as (current work block, blocks per set)
+    pub pomodoro: Option<(u32, u32)>,
+    /// Time left in the active break
+    pub break_remaining: Option<Duration>,
+    /// Whether input during the active break is holding up its countdown
+    pub break_interrupted: bool,
+    /// Postpones left per scheduled break kind, if they are limited
+    pub postpones_left: Vec<(String, u32)>,
+    /// When a timed pause ends
+    pub paused_until: Option<DateTime<Local>>,
+    /// When the focus block ends, in focus mode
+    pub focus_until: Option<DateTime<Local>>,
+    /// Length of the breaks missed during the focus block
+    pub break_debt: Duration,
+    /// Idle detection backend in use
+    pub idle: IdleStatus,
+    /// Whether the session is locked or switched away from
+    pub session_away: bool,
+    /// Applications holding back breaks, oldest first
+    pub inhibitors: Vec<Inhibitor>,
+    /// Working-hours status, if a schedule is configured
+    pub schedule: Option<ScheduleStatus>,
+    /// Active time-of-day override rule
+    pub rule: Option<String>,
+}
+
+/// An application holding back breaks, e.g. during a presentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inhibitor {
+    pub application: String,
+    pub reason: String,
+    /// Bus name of the holder; the inhibitor is released when it goes away
+    pub owner: Option<String>,
+}
+
+/// How idle detection is doing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdleStatus {
+    /// Not needed with the current settings
+    #[default]
+    Unused,
+    /// No backend works on this desktop
+    Unavailable,
+    /// Idle time comes from the named backend
+    Backend(&'static str),
+}
+
+/// Where the timer stands in the working-hours schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleStatus {
+    /// Whether it is currently working hours
+    pub working: bool,
+    /// When that changes next, if ever
+    pub until: Option<DateTime<Local>>,
+}
+
+/// Everything the timer knows, guarded by one lock
+struct TimerInner {
+    /// Configuration as loaded, before time-of-day overrides
+    base_config: Config,
+    /// Configuration in effect, with the active override applied
+    config: Config,
+    /// Name of the active override rule
+    rule: Option<String>,
+    state: TimerState,
+    /// Next deadline per scheduled break kind, keyed by name
+    next_breaks: HashMap<String, Instant>,
+    /// Current work block within the Pomodoro set (1-based)
+    pomodoro: u32,
+    break_end_time: Option<Instant>,
+    /// Full length of the active break
+    break_duration: std::time::Duration,
+    /// First input seen during the active break, while it holds up the
+    /// countdown
+    break_interrupted_at: Option<Instant>,
+    /// When a timed pause ends; a wall-clock time, like the user asked for
+    paused_until: Option<DateTime<Local>>,
+    /// Since when the user is away, idle or with the session locked, if
+    /// that is the only reason for the pause, so their return may end it
+    idle_paused: Option<Instant>,
+    /// Since when the session is locked or switched away from
+    away_since: Option<Instant>,
+    /// When the last stretch away was accounted for, as a natural break or
+    /// by `idle_return`, so it isn't counted twice
+    natural_at: Option<Instant>,
+    /// When the user came back and how long they were away, while they may
+    /// still count that as a break
+    pending_return: Option<(Instant, std::time::Duration)>,
+    /// When the focus block ends
+    focus_until: Option<DateTime<Local>>,
+    /// Longest break kind missed during the focus block and the total
+    /// length of all missed breaks in seconds
+    break_debt: Option<(String, u64)>,
+    /// Catch-up break held back by an inhibitor when its focus block ended,
+    /// with its length; it starts once breaks may fire again
+    catch_up: Option<(String, std::time::Duration)>,
+    /// Reported by the idle watcher, for status output
+    idle_status: IdleStatus,
+    /// Applications holding back breaks, by cookie
+    inhibitors: BTreeMap<u32, Inhibitor>,
+    /// Cookie for the next inhibitor
+    next_cookie: u32,
+    /// Wall-clock and monotonic time when the system went to sleep
+    suspended_at: Option<(DateTime<Local>, Instant)>,
+    /// When the user went idle, while interval countdowns stand still
+    inactive_since: Option<Instant>,
+    /// Break kinds already warned about in their current countdown
+    warned: HashSet<String>,
+    /// Times each break kind was postponed since it was last taken
+    postpones: HashMap<String, u32>,
+    /// When each break kind was last taken
+    last_breaks: HashMap<String, DateTime<Local>>,
+}
+
+impl TimerInner {
+    /// Validate and apply an action, returning the state it left
+    fn transition(&mut self, action: TimerAction) -> Result<TimerState, TimerError> {
+        let to = self.state.apply(&action)?;
+        if to != TimerState::Paused {
+            self.paused_until = None;
+            self.idle_paused = None;
+        }
+        if to != TimerState::Focus {
+            self.focus_until = None;
+            self.break_debt = None;
+        }
+        // Any break or focus block settles a held back catch-up break
+        if matches!(to, TimerState::InBreak(_) | TimerState::Focus) {
+            self.catch_up = None;
+        }
+        // The next break or postpone settles the time away
+        if matches!(to, TimerState::InBreak(_) | TimerState::Postponed(_)) {
+            self.pending_return = None;
+        }
+        Ok(std::mem::replace(&mut self.state, to))
+    }
+
+    /// Enter a break of a known kind
+    fn start_break(&mut self, name: &str, now: Instant) -> Result<TimerEvent, TimerError> {
+        let duration = std::time::Duration::from_secs(self.break_config(name)?.duration);
+        self.start_break_for(name, duration, now)
+    }
+
+    /// Enter a break of a known kind, lasting `duration`
+    fn start_break_for(
+        &mut self,
+        name: &str,
+        duration: std::time::Duration,
+        now: Instant,
+    ) -> Result<TimerEvent, TimerError> {
+        self.break_config(name)?;
+        self.transition(TimerAction::StartBreak(name.to_string()))?;
+        self.break_end_time = Some(now + duration);
+        self.break_duration = duration;
+        self.break_interrupted_at = None;
+        Ok(TimerEvent::BreakStarted {
+            name: name.to_string(),
+            duration,
+        })
+    }
+
+    /// Look up a break kind in the current configuration
+    fn break_config(&self, name: &str) -> Result<&BreakConfig, TimerError> {
+        self.config
+            .break_config(name)
+            .ok_or_else(|| TimerError::UnknownBreak(name.to_string()))
+    }
+
+    /// Postpones left for a break kind, or `None` without a limit
+    fn postpones_left(&self, name: &str) -> Option<u32> {
+        let postponed = self.postpones.get(name).copied().unwrap_or_default();
+        self.config.postpones_left(postponed)
+    }
+
+    /// Fail if a break kind used up its postpones
+    fn check_postpones(&self, name: &str) -> Result<(), TimerError> {
+        match self.postpones_left(name) {
+            Some(0) => Err(TimerError::PostponeLimit(name.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Restart the countdown after a break of kind `name` was taken
+    ///
+    /// A break also covers every shorter kind, so their countdowns restart
+    /// too. In Pomodoro mode, taking the break that closes the current work
+    /// block moves on to the next block instead.
+    fn break_taken(&mut self, name: &str, now: Instant, wall_now: DateTime<Local>) {
+        self.postpones.remove(name);
+        match self.config.mode {
+            ScheduleMode::Intervals => {
+                let Some(taken) = self.config.break_config(name) else {
+                    return;
+                };
+                let covered: Vec<_> = self
+                    .config
+                    .breaks
+                    .iter()
+                    .filter(|b| {
+                        b.name == name
+                            || (b.duration < taken.duration
+                                && self.next_breaks.contains_key(&b.name))
+                    })
+                    .map(|b| {
+                        // Measured from the slot that was due, if any, so an
+                        // interval as long as the time between slots doesn't
+                        // skip every other one
+                        let since = self
+                            .due_slot(b, now, wall_now)
+                            .and_then(|slot| (wall_now - slot).to_std().ok())
+                            .unwrap_or_default();
+                        let min_gap = minutes(b.interval).saturating_sub(since);
+                        (b.name.clone(), first_deadline(b, now, wall_now, min_gap))
+                    })
+                    .collect();
+                for (covered, next) in covered {
+                    self.postpones.remove(&covered);
+                    self.next_breaks.insert(covered, next);
+                }
+            }
+            ScheduleMode::Pomodoro => {
+                if self.config.pomodoro.break_after(self.pomodoro) == name {
+                    self.pomodoro = if self.pomodoro >= self.config.pomodoro.cycles {
+                        1
+                    } else {
+                        self.pomodoro + 1
+                    };
+                    self.next_breaks = scheduled(&self.config, self.pomodoro, now, wall_now)
+                        .map(|(b, next)| (b.name.clone(), next))
+                        .collect();
+                }
+            }
+        }
+    }
+
+    /// The clock-aligned slot at which a break kind came due, if it is due
+    fn due_slot(
+        &self,
+        break_config: &BreakConfig,
+        now: Instant,
+        wall_now: DateTime<Local>,
+    ) -> Option<DateTime<Local>> {
+        let due = *self.next_breaks.get(&break_config.name)?;
+        if due > now {
+            return None;
+        }
+        break_config
+            .timing
+            .last_until(wall_now + signed_until(now, due))
+    }
+
+    /// The longest break kind being away for `away` counts as
+    ///
+    /// In Pomodoro mode, only a work block that is over may end with time
+    /// away; stepping away in the middle of one doesn't skip ahead.
+    fn covered_by(&self, away: std::time::Duration, now: Instant) -> Option<&BreakConfig> {
+        self.config
+            .breaks
+            .iter()
+            .filter(|b| match self.config.mode {
+                ScheduleMode::Intervals => self.next_breaks.contains_key(&b.name),
+                ScheduleMode::Pomodoro => self.deadline(&b.name, now).is_some_and(|due| due <= now),
+            })
+            .filter(|b| away >= std::time::Duration::from_secs(b.duration))
+            .max_by_key(|b| b.duration)
+    }
+
+    /// Count being away for `away` as the longest break kind it covers
+    fn natural_break(
+        &mut self,
+        away: std::time::Duration,
+        now: Instant,
+        wall_now: DateTime<Local>,
+    ) -> Option<TimerEvent> {
+        let name = self.covered_by(away, now)?.name.clone();
+
+        self.break_taken(&name, now, wall_now);
+        self.last_breaks.insert(name.clone(), wall_now);
+        self.natural_at = Some(now);
+        Some(TimerEvent::NaturalBreak { name, idle: away })
+    }
+
+    /// End a pause while the user was away, as `idle_return` says
+    ///
+    /// With `Shift` and `Ask`, interval countdowns are pushed back by the
+    /// time away, unless `active_time_only` stopped them already, and the
+    /// stretch doesn't count as a natural break on its own.
+    fn return_from_idle(&mut self, now: Instant) -> Result<Vec<TimerEvent>, TimerError> {
+        let away = self.idle_paused.map_or(std::time::Duration::ZERO, |since| {
+            now.saturating_duration_since(since)
+        });
+        self.transition(TimerAction::Resume)?;
+        let mut events = vec![TimerEvent::Resumed];
+        if self.config.idle_return == IdleReturn::Reset {
+            return Ok(events);
+        }
+
+        if !self.config.active_time_only {
+            let shifted: Vec<String> = self
+                .next_breaks
+                .keys()
+                .filter(|name| !self.aligned(name))
+                .cloned()
+                .collect();
+            for name in shifted {
+                if let Some(next) = self.next_breaks.get_mut(&name) {
+                    *next += away;
+                }
+            }
+        }
+        self.natural_at = Some(now);
+
+        if self.config.idle_return == IdleReturn::Ask && self.covered_by(away, now).is_some() {
+            self.pending_return = Some((now, away));
+            events.push(TimerEvent::IdleReturned(away));
+        }
+        Ok(events)
+    }
+
+    /// Switch to a new effective configuration
+    ///
+    /// Newly scheduled break kinds start a full interval from now; kinds
+    /// that are no longer scheduled are dropped. Existing deadlines are
+    /// kept. Switching the scheduling mode starts a fresh Pomodoro set.
+    fn set_config(&mut self, config: Config, now: Instant, wall_now: DateTime<Local>) {
+        if self.config.mode != config.mode {
+            self.pomodoro = 1;
+        }
+        self.pomodoro = self.pomodoro.min(config.pomodoro.cycles.max(1));
+
+        let scheduled: HashMap<_, _> = scheduled(&config, self.pomodoro, now, wall_now)
+            .map(|(b, next)| (b.name.clone(), next))
+            .collect();
+        self.next_breaks.retain(|name, _| scheduled.contains_key(name));
+        for (name, next) in scheduled {
+            self.next_breaks.entry(name).or_insert(next);
+        }
+
+        self.config = config;
+    }
+
+    /// Apply the override rule active at `wall_now` if it changed
+    ///
+    /// Returns whether it did. Countdowns longer than a shortened interval
+    /// are cut down to it; lengthened intervals apply from the next break.
+    fn switch_rule(&mut self, wall_now: DateTime<Local>, now: Instant) -> bool {
+        let rule = self
+            .base_config
+            .active_override(wall_now)
+            .map(|o| o.name.clone());
+        if rule == self.rule {
+            return false;
+        }
+
+        self.set_config(self.base_config.at(wall_now), now, wall_now);
+        for (b, fresh) in scheduled(&self.config, self.pomodoro, now, wall_now) {
+            if let Some(next) = self.next_breaks.get_mut(&b.name) {
+                *next = (*next).min(fresh);
+            }
+        }
+        self.rule = rule;
+        true
+    }
+
+    /// Start every countdown from scratch, as at the start of a workday
+    fn start_fresh(&mut self, now: Instant, wall_now: DateTime<Local>) {
+        self.pomodoro = 1;
+        self.next_breaks = scheduled(&self.config, self.pomodoro, now, wall_now)
+            .map(|(b, next)| (b.name.clone(), next))
+            .collect();
+        self.postpones.clear();
+        self.warned.clear();
+        self.catch_up = None;
+    }
+
+    /// Whether the countdown of `name` is advancing
+    ///
+    /// With `active_time_only`, interval countdowns stand still while the
+    /// user is inactive. Clock-aligned ones always advance.
+    fn counting(&self, name: &str) -> bool {
+        self.inactive_since.is_none() || self.aligned(name)
+    }
+
+    /// End an idle gap at `back`, pushing back the countdowns that stood
+    /// still, and return whether there was one
+    fn active_again(&mut self, back: Instant) -> bool {
+        let Some(since) = self.inactive_since.take() else {
+            return false;
+        };
+        let inactive = back.saturating_duration_since(since);
+        let frozen: Vec<String> = self
+            .next_breaks
+            .keys()
+            .filter(|name| !self.aligned(name))
+            .cloned()
+            .collect();
+        for name in frozen {
+            if let Some(next) = self.next_breaks.get_mut(&name) {
+                *next += inactive;
+            }
+        }
+        true
+    }
+
+    /// Whether due breaks may start: the state allows it and no application
+    /// holds them back
+    fn fires_breaks(&self) -> bool {
+        self.state.fires_breaks() && self.inhibitors.is_empty()
+    }
+
+    /// Whether `name` is a clock-aligned break, due at a wall-clock slot
+    fn aligned(&self, name: &str) -> bool {
+        self.config.mode == ScheduleMode::Intervals
+            && self
+                .config
+                .break_config(name)
+                .is_some_and(|b| b.timing.is_aligned())
+    }
+
+    /// Deadline of a break kind as it stands at `now`
+    ///
+    /// A countdown standing still has its deadline pushed back by the
+    /// inactive time so far.
+    fn deadline(&self, name: &str, now: Instant) -> Option<Instant> {
+        let next = *self.next_breaks.get(name)?;
+        Some(match self.inactive_since {
+            Some(since) if !self.counting(name) => next + now.saturating_duration_since(since),
+            _ => next,
+        })
+    }
+
+    /// Deadlines of the countdowns that are advancing
+    fn counting_deadlines(&self) -> impl Iterator<Item = (&String, Instant)> {
+        self.next_breaks
+            .iter()
+            .filter(|(name, _)| self.counting(name))
+            .map(|(name, next)| (name, *next))
+    }
+
+    /// End of the active break as it stands at `now`
+    ///
+    /// While input holds up the countdown, `Pause` keeps the time left
+    /// frozen and `Restart` keeps it at the full break length.
+    fn break_end(&self, now: Instant) -> Option<Instant> {
+        let end = self.break_end_time?;
+        let Some(since) = self.break_interrupted_at else {
+            return Some(end);
+        };
+
+        Some(match self.config.break_activity {
+            BreakActivity::Restart => now + self.break_duration,
+            BreakActivity::Ignore | BreakActivity::Pause => {
+                end + now.saturating_duration_since(since)
+            }
+        })
+    }
+
+    /// Add a break missed during a focus block to the break debt
+    fn miss_break(&mut self, name: &str, now: Instant, wall_now: DateTime<Local>) {
+        let Some(missed) = self.config.break_config(name) else {
+            return;
+        };
+        let seconds = missed.duration;
+        let longest = match self.break_debt.take() {
+            Some((kind, debt)) => {
+                let longer = self
+                    .config
+                    .break_config(&kind)
+                    .is_some_and(|b| b.duration >= seconds);
+                (if longer { kind } else { name.to_string() }, debt + seconds)
+            }
+            None => (name.to_string(), seconds),
+        };
+        self.break_debt = Some(longest);
+        self.break_taken(name, now, wall_now);
+    }
+
+    /// End the focus block and start the catch-up break repaying its debt
+    ///
+    /// The catch-up break is a break of the longest kind missed, lasting
+    /// `repay` percent of the debt, but at least as long as that kind
+    /// normally is and at most `max_catch_up`. Without that kind in the
+    /// configuration any more, the block ends without one. While an
+    /// inhibitor holds back breaks, the catch-up break comes due right away
+    /// and starts once the last inhibitor is released.
+    fn end_focus(&mut self, now: Instant) -> Result<Vec<TimerEvent>, TimerError> {
+        let catch_up = self.break_debt.clone().and_then(|(name, debt)| {
+            let normal = self.config.break_config(&name)?.duration;
+            let focus = &self.config.focus;
+            let repay = debt * u64::from(focus.repay) / 100;
+            let seconds = repay.min(focus.max_catch_up).max(normal);
+            Some((name, std::time::Duration::from_secs(seconds)))
+        });
+        self.transition(TimerAction::EndFocus)?;
+
+        let mut events = vec![TimerEvent::FocusEnded(catch_up.as_ref().map(|(_, d)| *d))];
+        if let Some((name, duration)) = catch_up {
+            if self.fires_breaks() {
+                events.push(TimerEvent::BreakDue(name.clone()));
+                // The kind exists and the timer is running, so this can't fail
+                events.extend(self.start_break_for(&name, duration, now).ok());
+            } else {
+                if let Some(next) = self.next_breaks.get_mut(&name) {
+                    *next = now;
+                }
+                self.catch_up = Some((name, duration));
+            }
+        }
+        Ok(events)
+    }
+
+    /// Capture everything worth keeping across a restart
+    fn save(&self, wall_now: DateTime<Local>, now: Instant) -> SavedState {
+        let wall = |at: Instant| wall_now + signed_until(now, at);
+        SavedState {
+            saved_at: wall_now,
+            state: self.state.clone(),
+            next_breaks: self
+                .next_breaks
+                .keys()
+                .filter_map(|name| Some((name.clone(), wall(self.deadline(name, now)?))))
+                .collect(),
+            break_end: self.break_end(now).map(wall),
+            pomodoro: self.pomodoro,
+            postpones: self.postpones.clone(),
+            last_breaks: self.last_breaks.clone(),
+            paused_until: self.paused_until,
+            idle_paused: self.idle_paused.is_some(),
+            focus_until: self.focus_until,
+            break_debt: self.break_debt.clone(),
+        }
+    }
+
+    /// Pick up a state saved by a previous run
+    ///
+    /// Deadlines keep their wall-clock time, so restarting doesn't buy any
+    /// extra work time, and deadlines that passed meanwhile fire right away.
+    /// Being gone at least as long as a break counts as having taken it,
+    /// just like a suspend. A break that ran out meanwhile was taken.
+    ///
+    /// The previous run saved at least every [`HEARTBEAT`] and may have
+    /// lived until just before the next one, so only the time beyond that
+    /// counts as gone.
+    fn restore(&mut self, saved: SavedState, wall_now: DateTime<Local>, now: Instant) {
+        let away = (wall_now - saved.saved_at)
+            .to_std()
+            .unwrap_or_default()
+            .saturating_sub(HEARTBEAT);
+        let covers = |b: &BreakConfig| away >= std::time::Duration::from_secs(b.duration);
+        let deadline = |at: DateTime<Local>| now + (at - wall_now).to_std().unwrap_or_default();
+
+        self.pomodoro = saved.pomodoro.clamp(1, self.config.pomodoro.cycles.max(1));
+        self.postpones = saved.postpones;
+        self.last_breaks = saved.last_breaks;
+        self.next_breaks = scheduled(&self.config, self.pomodoro, now, wall_now)
+            .map(|(b, fresh)| {
+                let next = match saved.next_breaks.get(&b.name) {
+                    Some(at) if !covers(b) => deadline(*at),
+                    _ => fresh,
+                };
+                (b.name.clone(), next)
+            })
+            .collect();
+
+        self.state = match saved.state {
+            TimerState::InBreak(name) => {
+                let known = self.config.break_config(&name).is_some();
+                match saved.break_end {
+                    Some(end) if known && end > wall_now => {
+                        self.break_end_time = Some(deadline(end));
+                        let normal = self.config.break_config(&name).map_or(0, |b| b.duration);
+                        self.break_duration = std::time::Duration::from_secs(normal)
+                            .max((end - wall_now).to_std().unwrap_or_default());
+                        TimerState::InBreak(name)
+                    }
+                    end => {
+                        self.last_breaks
+                            .insert(name.clone(), end.unwrap_or(saved.saved_at));
+                        self.break_taken(&name, now, wall_now);
+                        TimerState::Running
+                    }
+                }
+            }
+            TimerState::Postponed(name) if self.config.break_config(&name).is_none() => {
+                TimerState::Running
+            }
+            state => state,
+        };
+        match self.state {
+            TimerState::Paused => {
+                self.paused_until = saved.paused_until;
+                self.idle_paused = saved.idle_paused.then_some(now);
+            }
+            TimerState::Focus => {
+                self.focus_until = saved.focus_until;
+                self.break_debt = saved.break_debt;
+            }
+            _ => {}
+        }
+    }
+
+    /// When the engine next has something to do, if anything
+    ///
+    /// That is the next working-hours or override rule change or the end
+    /// of a timed pause or focus block, plus the end of the active break,
+    /// or the next pre-break warning or advancing break deadline while
+    /// breaks may fire. In focus mode, missed deadlines add to the break
+    /// debt. Open-ended pauses without a schedule have nothing planned and
+    /// only wake up when poked.
+    fn next_wakeup(&self, now: Instant, wall_now: DateTime<Local>) -> Option<Instant> {
+        let schedule_change = [
+            self.base_config.schedule.next_change(wall_now),
+            self.base_config.next_override_change(wall_now),
+            self.paused_until,
+            self.focus_until,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|at| now + (at - wall_now).to_std().unwrap_or_default());
+
+        let warn_before = std::time::Duration::from_secs(self.config.notification_before_break);
+        let deadline = match self.state {
+            // Held up breaks continue when input stops, which pokes the loop
+            TimerState::InBreak(_) => self.break_end_time.filter(|_| {
+                self.break_interrupted_at.is_none()
+            }),
+            _ if self.fires_breaks() => self
+                .counting_deadlines()
+                .flat_map(|(name, next)| {
+                    let warning = next
+                        .checked_sub(warn_before)
+                        .filter(|at| *at > now && !self.warned.contains(name));
+                    [Some(next), warning]
+                })
+                .flatten()
+                .min(),
+            TimerState::Focus => self.counting_deadlines().map(|(_, next)| next).min(),
+            _ => None,
+        };
+
+        deadline.into_iter().chain(schedule_change).min()
+    }
+
+    /// The due break kind, if any
+    ///
+    /// When several kinds are due at once the longest one wins, since it
+    /// covers the shorter ones. A longer break due within `merge_window`
+    /// is started early instead, absorbing the one that is due.
+    fn due_break(&self, now: Instant) -> Option<String> {
+        // Don't trigger new breaks while paused, inhibited or already in one
+        if !self.fires_breaks() {
+            return None;
+        }
+        self.due(now)
+    }
+
+    /// The break kind that would be due regardless of the timer state
+    fn due(&self, now: Instant) -> Option<String> {
+        let due_by = |b: &&BreakConfig, at: Instant| {
+            self.counting(&b.name)
+                && self.next_breaks.get(&b.name).is_some_and(|next| at >= *next)
+        };
+        let due = self
+            .config
+            .breaks
+            .iter()
+            .filter(|b| due_by(b, now))
+            .max_by_key(|b| b.duration)?;
+
+        let window = now + minutes(self.config.merge_window);
+        let absorbing = self
+            .config
+            .breaks
+            .iter()
+            .filter(|b| b.duration > due.duration && due_by(b, window))
+            .max_by_key(|b| b.duration);
+
+        Some(absorbing.unwrap_or(due).name.clone())
+    }
 }
 
 /// Timer service that manages break intervals
+///
+/// All deadlines are monotonic instants, so wall-clock jumps (NTP
+/// corrections, manual changes, DST switches) neither fire nor delay
+/// breaks. The wall clock is only consulted to display when a deadline
+/// will be reached; after a jump those displayed times move with the clock
+/// while the countdowns stay put.
+///
+/// All state lives in one struct behind one lock. Every operation reads or
+/// updates it in a single critical section, so concurrent callers never see
+/// or produce half-applied changes.
 pub struct TimerService {
-    config: Arc<RwLock<Config>>,
-    state: Arc<RwLock<TimerState>>,
-    short_break_next: Arc<RwLock<DateTime<Local>>>,
-    long_break_next: Arc<RwLock<DateTime<Local>>>,
-    break_end_time: Arc<RwLock<Option<DateTime<Local>>>>,
+    clock: Arc<dyn Clock>,
+    inner: RwLock<TimerInner>,
+    events: broadcast::Sender<TimerEvent>,
+    /// Wakes the engine loop when deadlines may have moved
+    changed: Notify,
 }
 
 impl TimerService {
+    /// Create a timer service, restoring the state of the previous run
     pub fn new(config: Config) -> Self {
-        let now = Local::now();
-        let short_interval = Duration::minutes(config.short_break.interval as i64);
-        let long_interval = Duration::minutes(config.long_break.interval as i64);
+        let mut service = Self::with_clock(config, Arc::new(SystemClock));
+        if let Some(saved) = SavedState::load() {
+            let (wall_now, now) = (service.clock.now(), service.clock.monotonic());
+            service.inner.get_mut().restore(saved, wall_now, now);
+        }
+        service
+    }
+
+    /// Create a timer service driven by a custom clock
+    pub fn with_clock(base_config: Config, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.monotonic();
+        let wall_now = clock.now();
+        let config = base_config.at(wall_now);
+        let rule = base_config.active_override(wall_now).map(|o| o.name.clone());
+        let next_breaks = scheduled(&config, 1, now, wall_now)
+            .map(|(b, next)| (b.name.clone(), next))
+            .collect();
 
         Self {
-            config: Arc::new(RwLock::new(config)),
-            state: Arc::new(RwLock::new(TimerState::Running)),
-            short_break_next: Arc::new(RwLock::new(now + short_interval)),
-            long_break_next: Arc::new(RwLock::new(now + long_interval)),
-            break_end_time: Arc::new(RwLock::new(None)),
+            clock,
+            inner: RwLock::new(TimerInner {
+                base_config,
+                config,
+                rule,
+                state: TimerState::Running,
+                next_breaks,
+                pomodoro: 1,
+                break_end_time: None,
+                break_interrupted_at: None,
+                paused_until: None,
+                idle_paused: None,
+                focus_until: None,
+                break_debt: None,
+                catch_up: None,
+                idle_status: IdleStatus::default(),
+                inhibitors: BTreeMap::new(),
+                next_cookie: 1,
+                away_since: None,
+                natural_at: None,
+                pending_return: None,
+                break_duration: std::time::Duration::ZERO,
+                suspended_at: None,
+                inactive_since: None,
+                warned: HashSet::new(),
+                postpones: HashMap::new(),
+                last_breaks: HashMap::new(),
+            }),
+            events: broadcast::channel(64).0,
+            changed: Notify::new(),
+        }
+    }
+
+    /// Run the timer engine
+    ///
+    /// Sleeps until the next thing is due (pre-break warning, break start,
+    /// break end) instead of polling, and re-plans whenever an operation
+    /// moves the deadlines. The state is saved on every wakeup, and at
+    /// least every [`HEARTBEAT`], so a restart picks up where this run left
+    /// off and can tell how long nobody was counting. Never returns.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            self.tick().await;
+
+            let (saved, delay) = {
+                let inner = self.inner.read().await;
+                let now = self.clock.monotonic();
+                (
+                    inner.save(self.clock.now(), now),
+                    inner
+                        .next_wakeup(now, self.clock.now())
+                        .map(|at| at.saturating_duration_since(now)),
+                )
+            };
+            if let Err(e) = saved.save() {
+                eprintln!("Failed to save timer state: {}", e);
+            }
+
+            let delay = delay.map_or(HEARTBEAT, |delay| delay.clamp(MIN_WAKEUP, HEARTBEAT));
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = self.changed.notified() => {}
+            }
         }
     }
 
+    /// Make the engine loop re-plan its next wakeup
+    fn reschedule(&self) {
+        self.changed.notify_one();
+    }
+
+    /// Current configuration
+    pub async fn config(&self) -> Config {
+        self.inner.read().await.config.clone()
+    }
+
+    /// Receive every timer event from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<TimerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Clock driving this timer, for stamping what it reports
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    /// Tell subscribers about something that just happened
+    fn publish(&self, event: TimerEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+
     /// Get current timer state
     pub async fn state(&self) -> TimerState {
-        self.state.read().await.clone()
+        self.inner.read().await.state.clone()
+    }
+
+    /// Capture a consistent view of the whole timer
+    pub async fn snapshot(&self) -> TimerSnapshot {
+        let inner = self.inner.read().await;
+        let now = self.clock.monotonic();
+        let wall_now = self.clock.now();
+
+        let breaks: Vec<(String, Duration)> = inner
+            .config
+            .breaks
+            .iter()
+            .filter_map(|b| {
+                let next = inner.deadline(&b.name, now)?;
+                Some((b.name.clone(), signed_until(now, next)))
+            })
+            .collect();
+
+        let next_break_at = breaks
+            .iter()
+            .min_by_key(|(_, remaining)| *remaining)
+            .map(|(name, remaining)| (name.clone(), wall_now + *remaining));
+
+        let pomodoro = (inner.config.mode == ScheduleMode::Pomodoro)
+            .then_some((inner.pomodoro, inner.config.pomodoro.cycles));
+
+        let postpones_left = breaks
+            .iter()
+            .filter_map(|(name, _)| Some((name.clone(), inner.postpones_left(name)?)))
+            .collect();
+
+        let schedule = &inner.base_config.schedule;
+        let schedule = (!schedule.windows.is_empty()).then(|| ScheduleStatus {
+            working: schedule.is_active(wall_now),
+            until: schedule.next_change(wall_now),
+        });
+
+        TimerSnapshot {
+            state: inner.state.clone(),
+            breaks,
+            next_break_at,
+            pomodoro,
+            break_remaining: inner.break_end(now).map(|end| signed_until(now, end)),
+            break_interrupted: inner.break_interrupted_at.is_some(),
+            postpones_left,
+            paused_until: inner.paused_until,
+            focus_until: inner.focus_until,
+            break_debt: Duration::seconds(
+                inner.break_debt.as_ref().map_or(0, |(_, debt)| *debt as i64),
+            ),
+            idle: inner.idle_status,
+            session_away: inner.away_since.is_some(),
+            inhibitors: inner.inhibitors.values().cloned().collect(),
+            schedule,
+            rule: inner.rule.clone(),
+        }
+    }
+
+    /// Start a break
+    pub async fn start_break(&self, name: &str) -> Result<(), TimerError> {
+        let event = self
+            .inner
+            .write()
+            .await
+            .start_break(name, self.clock.monotonic())?;
+        self.publish(event);
+        self.reschedule();
+        Ok(())
+    }
+
+    /// Fire whatever is due: pre-break warnings, breaks whose deadline
+    /// passed, the end of the active break and working-hours changes
+    ///
+    /// Outside working hours the timer goes dormant, and each working
+    /// window starts with fresh countdowns. A break that is running when
+    /// working hours end may finish first.
+    ///
+    /// Checking and starting happen under one lock, so a break can't be
+    /// started twice or after it was postponed in between.
+    pub async fn tick(&self) {
+        let mut events = Vec::new();
+        {
+            let mut inner = self.inner.write().await;
+            let inner = &mut *inner;
+            let now = self.clock.monotonic();
+            let wall_now = self.clock.now();
+
+            if inner.switch_rule(wall_now, now) {
+                events.push(TimerEvent::RuleChanged(inner.rule.clone()));
+            }
+
+            let warn_before =
+                std::time::Duration::from_secs(inner.config.notification_before_break);
+
+            // Re-arm warnings for breaks whose deadline moved away again
+            let rearmed: Vec<String> = inner
+                .warned
+                .iter()
+                .filter(|name| {
+                    inner
+                        .deadline(name, now)
+                        .map_or(true, |next| next > now + warn_before)
+                })
+                .cloned()
+                .collect();
+            for name in rearmed {
+                inner.warned.remove(&name);
+            }
+
+            if inner.paused_until.is_some_and(|until| wall_now >= until)
+                && inner.transition(TimerAction::Resume).is_ok()
+            {
+                events.push(TimerEvent::PauseExpired);
+            }
+
+            if inner.state == TimerState::Focus {
+                if let Some(name) = inner.due(now) {
+                    inner.miss_break(&name, now, wall_now);
+                }
+                if inner.focus_until.is_some_and(|until| wall_now >= until) {
+                    if let Ok(ended) = inner.end_focus(now) {
+                        events.extend(ended);
+                    }
+                }
+            }
+
+            if let TimerState::InBreak(name) = inner.state.clone() {
+                if inner.break_end_time.is_some_and(|end| now >= end)
+                    && inner.break_interrupted_at.is_none()
+                    && inner.transition(TimerAction::EndBreak).is_ok()
+                {
+                    inner.break_end_time = None;
+                    inner.break_taken(&name, now, wall_now);
+                    inner.last_breaks.insert(name.clone(), wall_now);
+                    events.push(TimerEvent::BreakEnded(name));
+                }
+            }
+
+            if inner.config.schedule.is_active(wall_now) {
+                if inner.transition(TimerAction::ClockIn).is_ok() {
+                    inner.start_fresh(now, wall_now);
+                    events.push(TimerEvent::ClockedIn);
+                }
+            } else if inner.transition(TimerAction::ClockOut).is_ok() {
+                events.push(TimerEvent::ClockedOut);
+            }
+
+            // Nobody is there to take breaks while the session is locked
+            if inner.away_since.is_some()
+                && inner.state.fires_breaks()
+                && inner.transition(TimerAction::Pause).is_ok()
+            {
+                inner.idle_paused = inner.away_since;
+                events.push(TimerEvent::Paused);
+            }
+
+            if inner.fires_breaks() {
+                let soon: Vec<(String, Instant)> = inner
+                    .counting_deadlines()
+                    .filter(|(_, next)| *next > now && *next - now <= warn_before)
+                    .map(|(name, next)| (name.clone(), next))
+                    .collect();
+                for (name, next) in soon {
+                    if inner.warned.insert(name.clone()) {
+                        events.push(TimerEvent::PreBreakWarning {
+                            name,
+                            remaining: next - now,
+                        });
+                    }
+                }
+            }
+
+            if inner.fires_breaks() {
+                if let Some((name, duration)) = inner.catch_up.take() {
+                    events.push(TimerEvent::BreakDue(name.clone()));
+                    events.extend(inner.start_break_for(&name, duration, now).ok());
+                }
+            }
+
+            if let Some(name) = inner.due_break(now) {
+                events.push(TimerEvent::BreakDue(name.clone()));
+                if let Ok(event) = inner.start_break(&name, now) {
+                    events.push(event);
+                }
+            }
+        }
+
+        for event in events {
+            self.publish(event);
+        }
+    }
+
+    /// End the current break and reset its timer
+    ///
+    /// In Pomodoro mode, ending the break that closes the current work block
+    /// moves on to the next block instead.
+    pub async fn end_break(&self) -> Result<(), TimerError> {
+        self.finish_break(TimerAction::EndBreak).await
     }
 
-    /// Get time until next short break
-    pub async fn time_until_short_break(&self) -> Duration {
-        let next = *self.short_break_next.read().await;
-        next - Local::now()
+    /// Skip the current break
+    ///
+    /// Fails once the break used up its postpones.
+    pub async fn skip_break(&self) -> Result<(), TimerError> {
+        self.finish_break(TimerAction::Skip).await
     }
 
-    /// Get time until next long break
-    pub async fn time_until_long_break(&self) -> Duration {
-        let next = *self.long_break_next.read().await;
-        next - Local::now()
+    async fn finish_break(&self, action: TimerAction) -> Result<(), TimerError> {
+        let skipped = action == TimerAction::Skip;
+        let mut inner = self.inner.write().await;
+        if let (true, TimerState::InBreak(name)) = (skipped, &inner.state) {
+            inner.check_postpones(name)?;
+        }
+        let from = inner.transition(action)?;
+        inner.break_end_time = None;
+
+        if let TimerState::InBreak(name) = from {
+            let wall_now = self.clock.now();
+            inner.break_taken(&name, self.clock.monotonic(), wall_now);
+            if !skipped {
+                inner.last_breaks.insert(name.clone(), wall_now);
+            }
+            self.publish(if skipped {
+                TimerEvent::Skipped(name)
+            } else {
+                TimerEvent::BreakEnded(name)
+            });
+        }
+
+        self.reschedule();
+        Ok(())
     }
 
-    /// Get the next break that will occur
-    pub async fn next_break(&self) -> (BreakType, Duration) {
-        let short_time = self.time_until_short_break().await;
-        let long_time = self.time_until_long_break().await;
+    /// Postpone the next break of a kind
+    ///
+    /// Also ends that break if it is the one currently active. Each
+    /// postpone of the same break may be shorter than the last, and once
+    /// `max_postpones` is used up the break must be taken.
+    pub async fn postpone_break(&self, name: &str) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        inner.break_config(name)?;
+        if !inner.next_breaks.contains_key(name) {
+            return Err(TimerError::NotScheduled(name.to_string()));
+        }
+        inner.check_postpones(name)?;
+
+        inner.transition(TimerAction::Postpone(name.to_string()))?;
+        inner.break_end_time = None;
+        let postponed = inner.postpones.get(name).copied().unwrap_or_default();
+        let postpone_duration = minutes(inner.config.postpone_minutes(postponed));
+        inner.postpones.insert(name.to_string(), postponed + 1);
 
-        if short_time < long_time {
-            (BreakType::Short, short_time)
-        } else {
-            (BreakType::Long, long_time)
+        if let Some(next) = inner.next_breaks.get_mut(name) {
+            *next += postpone_duration;
         }
+
+        self.publish(TimerEvent::Postponed {
+            name: name.to_string(),
+            by: postpone_duration,
+        });
+        self.reschedule();
+        Ok(())
     }
 
-    /// Start a break
-    pub async fn start_break(&self, break_type: BreakType) {
-        let config = self.config.read().await;
-        let duration = match break_type {
-            BreakType::Short => config.short_break.duration,
-            BreakType::Long => config.long_break.duration,
-        };
+    /// Hold back breaks for a focus block
+    ///
+    /// Breaks that come due meanwhile are missed and add up to break debt,
+    /// which a longer catch-up break repays once the block is over. Without
+    /// a `duration`, the block lasts `focus.duration` minutes.
+    pub async fn start_focus(
+        &self,
+        duration: Option<std::time::Duration>,
+    ) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        let until = self.end_after(duration.unwrap_or(minutes(inner.config.focus.duration)));
 
-        *self.state.write().await = TimerState::InBreak(break_type);
-        *self.break_end_time.write().await = Some(
-            Local::now() + Duration::seconds(duration as i64)
+        inner.transition(TimerAction::StartFocus)?;
+        inner.focus_until = until;
+        inner.break_debt = None;
+        self.publish(TimerEvent::FocusStarted(until));
+        self.reschedule();
+        Ok(())
+    }
+
+    /// End the focus block early, starting the catch-up break if breaks
+    /// were missed
+    pub async fn end_focus(&self) -> Result<(), TimerError> {
+        let events = self.inner.write().await.end_focus(self.clock.monotonic())?;
+        for event in events {
+            self.publish(event);
+        }
+        self.reschedule();
+        Ok(())
+    }
+
+    /// Pause the timer until it is resumed
+    pub async fn pause(&self) -> Result<(), TimerError> {
+        self.pause_with(None, None).await
+    }
+
+    /// Pause the timer for a while, resuming automatically
+    pub async fn pause_for(&self, duration: std::time::Duration) -> Result<(), TimerError> {
+        self.pause_with(self.end_after(duration), None).await
+    }
+
+    /// Pause the timer until a wall-clock time, resuming automatically
+    pub async fn pause_until(&self, until: DateTime<Local>) -> Result<(), TimerError> {
+        self.pause_with(Some(until), None).await
+    }
+
+    /// Pause the timer while the user is away, which they have been for
+    /// `idle` already
+    ///
+    /// Unlike a pause the user asked for, [`Self::resume_from_idle`] ends
+    /// it.
+    pub async fn pause_while_idle(&self, idle: std::time::Duration) -> Result<(), TimerError> {
+        let now = self.clock.monotonic();
+        self.pause_with(None, Some(now.checked_sub(idle).unwrap_or(now))).await
+    }
+
+    /// Wall-clock time `duration` from now
+    ///
+    /// Too far out to ever come is the same as never, so that is `None`.
+    fn end_after(&self, duration: std::time::Duration) -> Option<DateTime<Local>> {
+        Duration::from_std(duration)
+            .ok()
+            .and_then(|duration| self.clock.now().checked_add_signed(duration))
+    }
+
+    /// Pause, or change how long an existing user pause lasts
+    async fn pause_with(
+        &self,
+        until: Option<DateTime<Local>>,
+        idle_since: Option<Instant>,
+    ) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        if idle_since.is_some() || inner.state != TimerState::Paused {
+            inner.transition(TimerAction::Pause)?;
+        }
+        inner.paused_until = until;
+        inner.idle_paused = idle_since;
+        self.publish(TimerEvent::Paused);
+        self.reschedule();
+        Ok(())
+    }
+
+    /// Resume the timer
+    pub async fn resume(&self) -> Result<(), TimerError> {
+        self.inner.write().await.transition(TimerAction::Resume)?;
+        self.publish(TimerEvent::Resumed);
+        self.reschedule();
+        Ok(())
+    }
+
+    /// Resume the timer if it is only paused because the user was away
+    ///
+    /// Countdowns go on as `idle_return` says. Input on the lock screen
+    /// doesn't count; the timer resumes once the session is back.
+    pub async fn resume_from_idle(&self) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        if inner.idle_paused.is_none() || inner.away_since.is_some() {
+            return Ok(());
+        }
+        for event in inner.return_from_idle(self.clock.monotonic())? {
+            self.publish(event);
+        }
+        self.reschedule();
+        Ok(())
+    }
+
+    /// Count the time the user was just away as a break after all
+    ///
+    /// For `idle_return: Ask`, once the user chose to. Only the latest
+    /// return can be counted, within [`RETURN_ANSWER_WINDOW`] and before
+    /// the next break or postpone.
+    pub async fn count_away_as_break(&self) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        let now = self.clock.monotonic();
+        let (_, away) = inner
+            .pending_return
+            .take()
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= RETURN_ANSWER_WINDOW)
+            .ok_or(TimerError::NoPendingReturn)?;
+        if let Some(event) = inner.natural_break(away, now, self.clock.now()) {
+            self.publish(event);
+            self.reschedule();
+        }
+        Ok(())
+    }
+
+    /// Hold back breaks until [`Self::uninhibit`] is called with the
+    /// returned cookie
+    ///
+    /// Countdowns keep running; a break that comes due meanwhile starts
+    /// once the last inhibitor is released. A break already running goes
+    /// on.
+    pub async fn inhibit(&self, application: String, reason: String, owner: Option<String>) -> u32 {
+        let mut inner = self.inner.write().await;
+        let cookie = inner.next_cookie;
+        inner.next_cookie = cookie.wrapping_add(1).max(1);
+        inner.inhibitors.insert(
+            cookie,
+            Inhibitor {
+                application,
+                reason,
+                owner,
+            },
         );
+        self.publish(TimerEvent::InhibitorsChanged);
+        self.reschedule();
+        cookie
+    }
+
+    /// Release the inhibitor holding `cookie`
+    pub async fn uninhibit(&self, cookie: u32) -> Result<(), TimerError> {
+        let mut inner = self.inner.write().await;
+        inner
+            .inhibitors
+            .remove(&cookie)
+            .ok_or(TimerError::UnknownInhibitor(cookie))?;
+        self.publish(TimerEvent::InhibitorsChanged);
+        self.reschedule();
+        Ok(())
     }
 
-    /// End the current break and reset timers
-    pub async fn end_break(&self) {
-        let state = self.state.read().await.clone();
+    /// Release every inhibitor held by `owner`, which went away
+    pub async fn release_inhibitors(&self, owner: &str) {
+        let mut inner = self.inner.write().await;
+        let before = inner.inhibitors.len();
+        inner
+            .inhibitors
+            .retain(|_, inhibitor| inhibitor.owner.as_deref() != Some(owner));
+        if inner.inhibitors.len() != before {
+            self.publish(TimerEvent::InhibitorsChanged);
+            self.reschedule();
+        }
+    }
+
+    /// Record how idle detection is doing, returning whether that changed
+    pub async fn set_idle_status(&self, status: IdleStatus) -> bool {
+        let mut inner = self.inner.write().await;
+        std::mem::replace(&mut inner.idle_status, status) != status
+    }
 
-        if let TimerState::InBreak(break_type) = state {
-            let config = self.config.read().await;
-            let now = Local::now();
+    /// Feed the user's current idle time into active-time accounting and
+    /// break enforcement
+    ///
+    /// With `active_time_only`, an idle gap of at least `idle_gap` stops the
+    /// interval countdowns from the moment the user went idle until they
+    /// are back. Shorter gaps count as active time.
+    ///
+    /// Unless `break_activity` is `Ignore`, input during a break holds up
+    /// its countdown until the user stops again.
+    pub async fn record_idle(&self, idle: std::time::Duration) {
+        let now = self.clock.monotonic();
+        let mut inner = self.inner.write().await;
+        let gap = std::time::Duration::from_secs(inner.config.idle_gap);
 
-            match break_type {
-                BreakType::Short => {
-                    let interval = Duration::minutes(config.short_break.interval as i64);
-                    *self.short_break_next.write().await = now + interval;
+        if let TimerState::InBreak(name) = inner.state.clone() {
+            let last_input = now.checked_sub(idle).unwrap_or(now);
+            let enforced = inner.config.break_activity != BreakActivity::Ignore;
+            let active = enforced && idle < BREAK_ACTIVITY_WINDOW;
+
+            match inner.break_interrupted_at {
+                None if active => {
+                    inner.break_interrupted_at = Some(last_input);
+                    self.publish(TimerEvent::BreakInterrupted(name));
+                    self.reschedule();
                 }
-                BreakType::Long => {
-                    let interval = Duration::minutes(config.long_break.interval as i64);
-                    *self.long_break_next.write().await = now + interval;
+                Some(_) if !active => {
+                    inner.break_end_time = inner.break_end(last_input);
+                    inner.break_interrupted_at = None;
+                    self.publish(TimerEvent::BreakContinued(name));
+                    self.reschedule();
                 }
+                _ => {}
             }
+        }
 
-            *self.state.write().await = TimerState::Running;
-            *self.break_end_time.write().await = None;
+        if inner.config.active_time_only && idle >= gap {
+            if inner.inactive_since.is_none() {
+                inner.inactive_since = Some(now.checked_sub(idle).unwrap_or(now));
+                self.reschedule();
+            }
+        } else if inner.active_again(now.checked_sub(idle).unwrap_or(now)) {
+            self.reschedule();
         }
     }
 
-    /// Skip the current break
-    pub async fn skip_break(&self) {
-        self.end_break().await;
+    /// Note that the user was active recently, for idle detection that
+    /// can't tell how long ago exactly
+    ///
+    /// Ends an idle gap for active-time accounting; input during a break
+    /// goes unseen.
+    pub async fn record_active(&self) {
+        let now = self.clock.monotonic();
+        if self.inner.write().await.active_again(now) {
+            self.reschedule();
+        }
     }
 
-    /// Postpone the next break
-    pub async fn postpone_break(&self, break_type: BreakType) {
-        let config = self.config.read().await;
-        let postpone_duration = Duration::minutes(config.postpone_duration as i64);
+    /// Note that the session was locked or switched away from
+    ///
+    /// The timer pauses, like for a user who went idle, and no break
+    /// starts until the session is back. A break already running goes on.
+    pub async fn session_away(&self) {
+        let mut inner = self.inner.write().await;
+        if inner.away_since.is_none() {
+            inner.away_since = Some(self.clock.monotonic());
+            self.publish(TimerEvent::SessionAway);
+            self.reschedule();
+        }
+    }
 
-        match break_type {
-            BreakType::Short => {
-                let mut next = self.short_break_next.write().await;
-                *next = *next + postpone_duration;
-            }
-            BreakType::Long => {
-                let mut next = self.long_break_next.write().await;
-                *next = *next + postpone_duration;
+    /// Note that the session is unlocked and active again
+    ///
+    /// Ends the pause while away, and being away at least as long as a
+    /// break counts as having taken it, like an idle stretch.
+    pub async fn session_back(&self) {
+        let away = {
+            let mut inner = self.inner.write().await;
+            let Some(since) = inner.away_since.take() else {
+                return;
+            };
+            self.publish(TimerEvent::SessionBack);
+            if inner.idle_paused.is_some() {
+                let now = self.clock.monotonic();
+                for event in inner.return_from_idle(now).unwrap_or_default() {
+                    self.publish(event);
+                }
             }
+            self.reschedule();
+            self.clock.monotonic().saturating_duration_since(since)
+        };
+        self.idle_ended(away).await;
+    }
+
+    /// Account for an idle stretch that just ended
+    ///
+    /// Being away at least as long as a break counts as a natural break of
+    /// that kind: the longest kind covered restarts its countdown, and with
+    /// it every shorter kind. In Pomodoro mode, it only ends a work block
+    /// that is already over. Idle time during a break or outside working
+    /// hours doesn't count, and neither does a stretch that already counted
+    /// as a locked session. Unless `idle_return` is `Reset`, a stretch that
+    /// paused the timer is left to [`Self::resume_from_idle`].
+    pub async fn idle_ended(&self, idle: std::time::Duration) {
+        let mut inner = self.inner.write().await;
+        if matches!(inner.state, TimerState::InBreak(_) | TimerState::OffHours) {
+            return;
+        }
+        if inner.idle_paused.is_some() && inner.config.idle_return != IdleReturn::Reset {
+            return;
+        }
+        let now = self.clock.monotonic();
+        let started = now.checked_sub(idle).unwrap_or(now);
+        if inner.natural_at.is_some_and(|at| at > started) {
+            return;
         }
 
-        *self.state.write().await = TimerState::Postponed;
+        if let Some(event) = inner.natural_break(idle, now, self.clock.now()) {
+            self.publish(event);
+            self.reschedule();
+        }
     }
 
-    /// Pause the timer (e.g., when user is idle)
-    pub async fn pause(&self) {
-        *self.state.write().await = TimerState::Paused;
+    /// Record that the system is about to suspend
+    ///
+    /// A repeated notification keeps the time of the first one.
+    pub async fn prepare_for_sleep(&self) {
+        let mut inner = self.inner.write().await;
+        if inner.suspended_at.is_none() {
+            inner.suspended_at = Some((self.clock.now(), self.clock.monotonic()));
+        }
     }
 
-    /// Resume the timer
-    pub async fn resume(&self) {
-        *self.state.write().await = TimerState::Running;
+    /// Whether a suspend was recorded and the system hasn't woken up yet
+    #[cfg(test)]
+    pub async fn suspended(&self) -> bool {
+        self.inner.read().await.suspended_at.is_some()
     }
 
-    /// Check if it's time for a break
-    pub async fn check_break_time(&self) -> Option<BreakType> {
-        let state = self.state.read().await;
+    /// Account for a suspend that just ended
+    ///
+    /// A suspend at least as long as a break counts as a natural break of
+    /// the longest kind it covers, just like time away. Shorter suspends
+    /// just pause the countdown. The monotonic clock usually stands still during suspend
+    /// already; whatever part of the sleep it did see is added back onto
+    /// the deadlines here. Clock-aligned breaks move on to their next slot,
+    /// since their deadline is a wall-clock time.
+    pub async fn wake_from_sleep(&self) {
+        let mut inner = self.inner.write().await;
+        let Some((wall_at, monotonic_at)) = inner.suspended_at.take() else {
+            return;
+        };
 
-        // Don't trigger new breaks if already in one
-        if !matches!(*state, TimerState::Running) {
-            return None;
+        let now = self.clock.monotonic();
+        let wall_now = self.clock.now();
+        let frozen = now.saturating_duration_since(monotonic_at);
+        for next in inner.next_breaks.values_mut() {
+            *next += frozen;
+        }
+        if let Some(end) = inner.break_end_time.as_mut() {
+            *end += frozen;
         }
 
-        let now = Local::now();
-        let short_next = *self.short_break_next.read().await;
-        let long_next = *self.long_break_next.read().await;
+        let slept = (wall_now - wall_at).to_std().unwrap_or_default();
+        if let TimerState::InBreak(name) = inner.state.clone() {
+            let covered = inner
+                .config
+                .break_config(&name)
+                .is_some_and(|b| slept >= std::time::Duration::from_secs(b.duration));
+            if covered && inner.transition(TimerAction::EndBreak).is_ok() {
+                inner.break_end_time = None;
+                inner.break_taken(&name, now, wall_now);
+                inner.last_breaks.insert(name.clone(), wall_now);
+                self.publish(TimerEvent::BreakEnded(name));
+            }
+        }
 
-        if now >= long_next {
-            Some(BreakType::Long)
-        } else if now >= short_next {
-            Some(BreakType::Short)
-        } else {
-            None
+        if !matches!(inner.state, TimerState::InBreak(_) | TimerState::OffHours) {
+            if let Some(event) = inner.natural_break(slept, now, wall_now) {
+                self.publish(event);
+            }
         }
+
+        if inner.config.mode == ScheduleMode::Intervals {
+            let aligned: Vec<_> = scheduled(&inner.config, inner.pomodoro, now, wall_now)
+                .filter(|(b, _)| b.timing.is_aligned())
+                .map(|(b, next)| (b.name.clone(), next))
+                .collect();
+            inner.next_breaks.extend(aligned);
+        }
+        self.reschedule();
     }
 
     /// Update configuration
     pub async fn update_config(&self, config: Config) {
-        *self.config.write().await = config;
+        let now = self.clock.monotonic();
+        let wall_now = self.clock.now();
+        let mut inner = self.inner.write().await;
+
+        let rule = config.active_override(wall_now).map(|o| o.name.clone());
+        let rule_changed = rule != inner.rule;
+        inner.rule = rule;
+        inner.set_config(config.at(wall_now), now, wall_now);
+        inner.base_config = config;
+        self.publish(TimerEvent::ConfigChanged);
+        if rule_changed {
+            self.publish(TimerEvent::RuleChanged(inner.rule.clone()));
+        }
+        self.reschedule();
+    }
+}
+
+/// Input this recent during a break counts as activity
+///
+/// The idle watcher samples idle time at least every second during
+/// enforced breaks, so this catches any input between two samples.
+pub const BREAK_ACTIVITY_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long after coming back the user may still count their time away as
+/// a break, with `idle_return: Ask`
+pub const RETURN_ANSWER_WINDOW: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Shortest sleep of the engine loop, so a deadline that is already due
+/// can't make it spin
+const MIN_WAKEUP: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Longest sleep of the engine loop, so the saved state tells when this
+/// run was last alive
+const HEARTBEAT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Signed time from `now` until a monotonic deadline
+fn signed_until(now: Instant, deadline: Instant) -> Duration {
+    if deadline >= now {
+        Duration::from_std(deadline - now).unwrap_or(Duration::MAX)
+    } else {
+        -Duration::from_std(now - deadline).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Break kinds that should have a deadline, with the deadline a countdown
+/// started at `now` would have
+///
+/// In interval mode that is every enabled kind. In Pomodoro mode it is just
+/// the break closing work block `pomodoro`, due one work block from now.
+fn scheduled(
+    config: &Config,
+    pomodoro: u32,
+    now: Instant,
+    wall_now: DateTime<Local>,
+) -> impl Iterator<Item = (&BreakConfig, Instant)> {
+    let pomodoro_break = match config.mode {
+        ScheduleMode::Intervals => None,
+        ScheduleMode::Pomodoro => Some(config.pomodoro.break_after(pomodoro)),
+    };
+
+    config.breaks.iter().filter_map(move |b| match pomodoro_break {
+        None if b.enabled => Some((b, first_deadline(b, now, wall_now, Default::default()))),
+        None => None,
+        Some(name) if b.name == name => Some((b, now + minutes(config.pomodoro.work))),
+        Some(_) => None,
+    })
+}
+
+/// Deadline of a break kind whose countdown starts at `now`
+///
+/// Clock-aligned kinds are due at their first slot on the wall clock at
+/// least `min_gap` from now, all others one interval from now.
+fn first_deadline(
+    break_config: &BreakConfig,
+    now: Instant,
+    wall_now: DateTime<Local>,
+    min_gap: std::time::Duration,
+) -> Instant {
+    let earliest = wall_now + Duration::from_std(min_gap).unwrap_or_default();
+    match break_config.timing.first_from(earliest) {
+        Some(at) => now + (at - wall_now).to_std().unwrap_or_default(),
+        None => now + minutes(break_config.interval),
+    }
+}
+
+/// Convert a config value in minutes to a monotonic duration
+fn minutes(minutes: u64) -> std::time::Duration {
+    std::time::Duration::from_secs(minutes * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::config::BreakTiming;
+    use chrono::TimeZone;
+
+    /// A timer on a manual clock, starting on a Monday morning
+    fn timer(config: Config) -> (TimerService, Arc<ManualClock>) {
+        let start = Local.with_ymd_and_hms(2026, 10, 19, 10, 0, 0).unwrap();
+        let clock = Arc::new(ManualClock::new(start));
+        (TimerService::with_clock(config, clock.clone()), clock)
+    }
+
+    async fn time_left(timer: &TimerService, name: &str) -> Duration {
+        let snapshot = timer.snapshot().await;
+        let left = snapshot.breaks.into_iter().find(|(b, _)| b == name);
+        left.map(|(_, left)| left).unwrap()
+    }
+
+    /// Events published since the last call
+    fn published(events: &mut broadcast::Receiver<TimerEvent>) -> Vec<TimerEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_manual_clock_drives_breaks() {
+        let (timer, clock) = timer(Config::default());
+        let mut events = timer.subscribe();
+
+        clock.advance(Duration::minutes(19));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(1));
+
+        clock.advance(Duration::minutes(1));
+        timer.tick().await;
+        clock.advance(Duration::seconds(20));
+        timer.tick().await;
+        let short = "short".to_string();
+        assert!(published(&mut events).ends_with(&[
+            TimerEvent::BreakDue(short.clone()),
+            TimerEvent::BreakStarted {
+                name: short.clone(),
+                duration: std::time::Duration::from_secs(20),
+            },
+            TimerEvent::BreakEnded(short),
+        ]));
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(20));
+    }
+
+    #[test]
+    fn test_transition_table() {
+        use TimerAction as A;
+        use TimerState as S;
+        let short = || "short".to_string();
+
+        assert_eq!(
+            S::Running.apply(&A::StartBreak(short())),
+            Ok(S::InBreak(short()))
+        );
+        assert_eq!(S::InBreak(short()).apply(&A::Skip), Ok(S::Running));
+        assert_eq!(
+            S::InBreak(short()).apply(&A::Postpone(short())),
+            Ok(S::Postponed(short()))
+        );
+        assert_eq!(S::Postponed(short()).apply(&A::Pause), Ok(S::Paused));
+        assert_eq!(S::Paused.apply(&A::Resume), Ok(S::Running));
+        assert_eq!(S::Focus.apply(&A::ClockOut), Ok(S::OffHours));
+        assert_eq!(S::OffHours.apply(&A::ClockIn), Ok(S::Running));
+
+        for (from, action) in [
+            (S::InBreak(short()), A::Pause),
+            (S::InBreak(short()), A::Postpone("long".to_string())),
+            (S::Running, A::Resume),
+            (S::Focus, A::StartBreak(short())),
+            (S::OffHours, A::StartFocus),
+        ] {
+            assert_eq!(
+                from.apply(&action),
+                Err(TimerError::InvalidTransition { from, action })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transition_leaves_state_alone() {
+        let (timer, _) = timer(Config::default());
+        timer.start_break("short").await.unwrap();
+
+        assert!(timer.pause().await.is_err());
+        assert_eq!(
+            timer.state().await,
+            TimerState::InBreak("short".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wall_clock_jumps_neither_fire_nor_delay_breaks() {
+        let (timer, clock) = timer(Config::default());
+        clock.advance(Duration::minutes(10));
+
+        clock.set(clock.now() + Duration::hours(2));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(10));
+
+        clock.set(clock.now() - Duration::hours(5));
+        timer.tick().await;
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(10));
+
+        clock.advance(Duration::minutes(10));
+        timer.tick().await;
+        assert_eq!(
+            timer.state().await,
+            TimerState::InBreak("short".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_longer_break_due_soon_absorbs_due_one() {
+        let mut config = Config::default();
+        config.breaks[0].interval = 58;
+        let (timer, clock) = timer(config);
+
+        clock.advance(Duration::minutes(58));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::InBreak("long".to_string()));
+
+        timer.end_break().await.unwrap();
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(58));
+        assert_eq!(time_left(&timer, "long").await, Duration::minutes(60));
+    }
+
+    #[tokio::test]
+    async fn test_merge_window_of_zero_keeps_breaks_apart() {
+        let mut config = Config::default();
+        config.breaks[0].interval = 58;
+        config.merge_window = 0;
+        let (timer, clock) = timer(config);
+
+        clock.advance(Duration::minutes(58));
+        timer.tick().await;
+        assert_eq!(
+            timer.state().await,
+            TimerState::InBreak("short".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_postpones_shrink_and_run_out() {
+        let mut config = Config::default();
+        config.postpone_durations = vec![5, 2];
+        config.max_postpones = Some(2);
+        let (timer, clock) = timer(config);
+
+        clock.advance(Duration::minutes(20));
+        timer.tick().await;
+        timer.postpone_break("short").await.unwrap();
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(5));
+
+        clock.advance(Duration::minutes(5));
+        timer.tick().await;
+        timer.postpone_break("short").await.unwrap();
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(2));
+
+        clock.advance(Duration::minutes(2));
+        timer.tick().await;
+        let limit = Err(TimerError::PostponeLimit("short".to_string()));
+        assert_eq!(timer.postpone_break("short").await, limit);
+        assert_eq!(timer.skip_break().await, limit);
+
+        // Taking the break restores the postpones
+        timer.end_break().await.unwrap();
+        let snapshot = timer.snapshot().await;
+        assert!(snapshot.postpones_left.contains(&("short".to_string(), 2)));
+    }
+
+    #[tokio::test]
+    async fn test_focus_repays_missed_breaks_afterwards() {
+        let (timer, clock) = timer(Config::default());
+        let mut events = timer.subscribe();
+        timer.start_focus(Some(minutes(60))).await.unwrap();
+
+        for _ in 0..3 {
+            clock.advance(Duration::minutes(20));
+            timer.tick().await;
+        }
+
+        // Two short breaks and a long one were missed
+        let catch_up = std::time::Duration::from_secs(20 + 20 + 300);
+        assert_eq!(timer.state().await, TimerState::InBreak("long".to_string()));
+        assert!(published(&mut events).ends_with(&[
+            TimerEvent::FocusEnded(Some(catch_up)),
+            TimerEvent::BreakDue("long".to_string()),
+            TimerEvent::BreakStarted {
+                name: "long".to_string(),
+                duration: catch_up,
+            },
+        ]));
+    }
+
+    #[tokio::test]
+    async fn test_focus_catch_up_waits_for_inhibitors() {
+        let (timer, clock) = timer(Config::default());
+        let mut events = timer.subscribe();
+        timer.start_focus(Some(minutes(60))).await.unwrap();
+        let cookie = timer
+            .inhibit("slides".to_string(), "presenting".to_string(), None)
+            .await;
+
+        for _ in 0..3 {
+            clock.advance(Duration::minutes(20));
+            timer.tick().await;
+        }
+        clock.advance(Duration::minutes(5));
+        timer.tick().await;
+
+        let catch_up = std::time::Duration::from_secs(20 + 20 + 300);
+        assert_eq!(timer.state().await, TimerState::Running);
+        let held = published(&mut events);
+        assert!(held.contains(&TimerEvent::FocusEnded(Some(catch_up))));
+        assert!(!held
+            .iter()
+            .any(|event| matches!(event, TimerEvent::BreakStarted { .. })));
+
+        timer.uninhibit(cookie).await.unwrap();
+        timer.tick().await;
+        assert_eq!(
+            published(&mut events),
+            [
+                TimerEvent::InhibitorsChanged,
+                TimerEvent::BreakDue("long".to_string()),
+                TimerEvent::BreakStarted {
+                    name: "long".to_string(),
+                    duration: catch_up,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inhibitors_hold_back_due_breaks_until_all_are_released() {
+        let (timer, clock) = timer(Config::default());
+        let slides = timer
+            .inhibit("slides".to_string(), "presenting".to_string(), None)
+            .await;
+        let owner = Some(":1.42".to_string());
+        timer
+            .inhibit("player".to_string(), "video".to_string(), owner)
+            .await;
+        assert_eq!(timer.snapshot().await.inhibitors.len(), 2);
+
+        clock.advance(Duration::minutes(20));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+
+        timer.uninhibit(slides).await.unwrap();
+        assert_eq!(
+            timer.uninhibit(slides).await,
+            Err(TimerError::UnknownInhibitor(slides))
+        );
+        timer.release_inhibitors(":1.7").await;
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+
+        timer.release_inhibitors(":1.42").await;
+        assert!(timer.snapshot().await.inhibitors.is_empty());
+        timer.tick().await;
+        let short = TimerState::InBreak("short".to_string());
+        assert_eq!(timer.state().await, short);
+    }
+
+    #[tokio::test]
+    async fn test_focus_ends_without_catch_up_when_missed_kind_is_gone() {
+        let config = Config::default();
+        let (timer, clock) = timer(config.clone());
+        let mut events = timer.subscribe();
+        timer.start_focus(None).await.unwrap();
+        clock.advance(Duration::minutes(20));
+        timer.tick().await;
+
+        let mut without_short = config;
+        without_short.breaks.retain(|b| b.name != "short");
+        timer.update_config(without_short).await;
+        published(&mut events);
+
+        timer.end_focus().await.unwrap();
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(published(&mut events), [TimerEvent::FocusEnded(None)]);
+    }
+
+    #[tokio::test]
+    async fn test_timed_pause_expires() {
+        let (timer, clock) = timer(Config::default());
+        let mut events = timer.subscribe();
+        timer.pause_for(minutes(10)).await.unwrap();
+        let until = clock.now() + Duration::minutes(10);
+        assert_eq!(timer.snapshot().await.paused_until, Some(until));
+
+        clock.advance(Duration::minutes(9));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Paused);
+
+        clock.advance(Duration::minutes(1));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(timer.snapshot().await.paused_until, None);
+        assert_eq!(
+            published(&mut events),
+            [TimerEvent::Paused, TimerEvent::PauseExpired]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_input_during_break_holds_up_its_countdown() {
+        let short = || "short".to_string();
+        // Seconds left while held up, and once input stopped two seconds ago
+        let cases = [
+            (BreakActivity::Pause, 15, 13),
+            (BreakActivity::Restart, 20, 18),
+        ];
+        for (activity, held, left) in cases {
+            let mut config = Config::default();
+            config.break_activity = activity;
+            let (timer, clock) = timer(config);
+            let mut events = timer.subscribe();
+            clock.advance(Duration::minutes(20));
+            timer.tick().await;
+            published(&mut events);
+
+            clock.advance(Duration::seconds(5));
+            timer.record_idle(std::time::Duration::ZERO).await;
+            clock.advance(Duration::seconds(30));
+            timer.tick().await;
+            let snapshot = timer.snapshot().await;
+            assert!(snapshot.break_interrupted);
+            assert_eq!(snapshot.break_remaining, Some(Duration::seconds(held)));
+
+            // The countdown goes on from the last input
+            timer.record_idle(BREAK_ACTIVITY_WINDOW).await;
+            let snapshot = timer.snapshot().await;
+            assert!(!snapshot.break_interrupted);
+            assert_eq!(snapshot.break_remaining, Some(Duration::seconds(left)));
+
+            clock.advance(Duration::seconds(left));
+            timer.tick().await;
+            assert_eq!(
+                published(&mut events),
+                [
+                    TimerEvent::BreakInterrupted(short()),
+                    TimerEvent::BreakContinued(short()),
+                    TimerEvent::BreakEnded(short()),
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_time_only_freezes_countdowns_while_idle() {
+        let mut config = Config::default();
+        config.active_time_only = true;
+        let (timer, clock) = timer(config);
+
+        // Gaps shorter than `idle_gap` count as active time
+        clock.advance(Duration::minutes(5));
+        timer.record_idle(std::time::Duration::from_secs(59)).await;
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(15));
+
+        // A longer gap stops the countdown from when the user went idle
+        clock.advance(Duration::minutes(5));
+        timer.record_idle(minutes(5)).await;
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(15));
+        clock.advance(Duration::minutes(10));
+        timer.tick().await;
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(15));
+
+        timer.record_idle(std::time::Duration::ZERO).await;
+        clock.advance(Duration::minutes(5));
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(10));
+        assert_eq!(time_left(&timer, "long").await, Duration::minutes(50));
+    }
+
+    /// Go idle after ten minutes of work, for `away`, and come back
+    async fn away_and_back(timer: &TimerService, clock: &ManualClock, away: Duration) {
+        clock.advance(Duration::minutes(10));
+        timer
+            .pause_while_idle(std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        clock.advance(away);
+        timer.resume_from_idle().await.unwrap();
+        timer.idle_ended(away.to_std().unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_idle_return_reset_restarts_covered_breaks() {
+        let (timer, clock) = timer(Config::default());
+        away_and_back(&timer, &clock, Duration::minutes(2)).await;
+
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(20));
+        assert_eq!(time_left(&timer, "long").await, Duration::minutes(48));
+    }
+
+    #[tokio::test]
+    async fn test_idle_return_shift_pushes_countdowns_back() {
+        let mut config = Config::default();
+        config.idle_return = IdleReturn::Shift;
+        let (timer, clock) = timer(config);
+        away_and_back(&timer, &clock, Duration::minutes(2)).await;
+
+        assert_eq!(timer.state().await, TimerState::Running);
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(10));
+        assert_eq!(time_left(&timer, "long").await, Duration::minutes(50));
+    }
+
+    #[tokio::test]
+    async fn test_idle_return_ask_counts_time_away_only_once() {
+        let mut config = Config::default();
+        config.idle_return = IdleReturn::Ask;
+        let (timer, clock) = timer(config);
+        let mut events = timer.subscribe();
+        away_and_back(&timer, &clock, Duration::minutes(2)).await;
+        assert!(published(&mut events).contains(&TimerEvent::IdleReturned(minutes(2))));
+
+        clock.advance(Duration::minutes(1));
+        timer.count_away_as_break().await.unwrap();
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(20));
+        assert_eq!(
+            timer.count_away_as_break().await,
+            Err(TimerError::NoPendingReturn)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_return_ask_expires() {
+        let mut config = Config::default();
+        config.idle_return = IdleReturn::Ask;
+        let (timer, clock) = timer(config);
+        away_and_back(&timer, &clock, Duration::minutes(2)).await;
+
+        clock.advance(Duration::from_std(RETURN_ANSWER_WINDOW).unwrap() + Duration::seconds(1));
+        assert_eq!(
+            timer.count_away_as_break().await,
+            Err(TimerError::NoPendingReturn)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_counts_only_time_beyond_heartbeat_as_away() {
+        let (before, clock) = timer(Config::default());
+        clock.advance(Duration::minutes(10));
+        let saved = before
+            .inner
+            .read()
+            .await
+            .save(clock.now(), clock.monotonic());
+
+        // Killed right before the next save, restarted right away
+        clock.advance(Duration::from_std(HEARTBEAT).unwrap() + Duration::seconds(10));
+        let after = TimerService::with_clock(Config::default(), clock.clone());
+        let (wall_now, now) = (clock.now(), clock.monotonic());
+        after.inner.write().await.restore(saved, wall_now, now);
+
+        assert_eq!(time_left(&after, "short").await, Duration::seconds(530));
+        assert_eq!(time_left(&after, "long").await, Duration::seconds(2930));
+    }
+
+    #[tokio::test]
+    async fn test_hourly_break_keeps_every_slot_with_hour_long_interval() {
+        let mut config = Config::default();
+        config.breaks[0].enabled = false;
+        config.breaks[1].timing = BreakTiming::Hourly(50);
+        let (timer, clock) = timer(config);
+
+        for _ in 0..3 {
+            clock.advance(Duration::minutes(50));
+            timer.tick().await;
+            assert_eq!(timer.state().await, TimerState::InBreak("long".to_string()));
+            clock.advance(Duration::minutes(5));
+            timer.tick().await;
+            assert_eq!(time_left(&timer, "long").await, Duration::minutes(55));
+            clock.advance(Duration::minutes(5));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_long_suspend_counts_as_natural_break() {
+        let mut config = Config::default();
+        config.max_postpones = Some(2);
+        let (timer, clock) = timer(config);
+        let mut events = timer.subscribe();
+
+        clock.advance(Duration::minutes(20));
+        timer.tick().await;
+        timer.postpone_break("short").await.unwrap();
+        timer.prepare_for_sleep().await;
+        clock.advance(Duration::minutes(6));
+        timer.wake_from_sleep().await;
+
+        let natural = TimerEvent::NaturalBreak {
+            name: "long".to_string(),
+            idle: minutes(6),
+        };
+        assert!(published(&mut events).ends_with(&[natural]));
+        let snapshot = timer.snapshot().await;
+        assert!(snapshot.postpones_left.contains(&("short".to_string(), 2)));
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(20));
+        assert_eq!(time_left(&timer, "long").await, Duration::minutes(60));
+    }
+
+    #[tokio::test]
+    async fn test_pomodoro_cycles_back_to_first_block_after_long_break() {
+        let mut config = Config::default();
+        config.mode = ScheduleMode::Pomodoro;
+        let (timer, clock) = timer(config);
+
+        for block in 1..=4 {
+            assert_eq!(timer.snapshot().await.pomodoro, Some((block, 4)));
+            clock.advance(Duration::minutes(25));
+            timer.tick().await;
+            let kind = if block == 4 { "long" } else { "short" };
+            assert_eq!(timer.state().await, TimerState::InBreak(kind.to_string()));
+            timer.end_break().await.unwrap();
+        }
+
+        assert_eq!(timer.snapshot().await.pomodoro, Some((1, 4)));
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(25));
+    }
+
+    #[tokio::test]
+    async fn test_pomodoro_block_ends_with_time_away_only_once_it_is_over() {
+        let mut config = Config::default();
+        config.mode = ScheduleMode::Pomodoro;
+        let (timer, clock) = timer(config);
+        let mut events = timer.subscribe();
+
+        clock.advance(Duration::minutes(10));
+        timer.idle_ended(minutes(1)).await;
+        assert!(published(&mut events).is_empty());
+        assert_eq!(timer.snapshot().await.pomodoro, Some((1, 4)));
+        assert_eq!(time_left(&timer, "short").await, Duration::minutes(15));
+
+        // Away past the end of the block
+        timer
+            .pause_while_idle(std::time::Duration::ZERO)
+            .await
+            .unwrap();
+        clock.advance(Duration::minutes(20));
+        timer.resume_from_idle().await.unwrap();
+        timer.idle_ended(minutes(20)).await;
+        assert!(published(&mut events).contains(&TimerEvent::NaturalBreak {
+            name: "short".to_string(),
+            idle: minutes(20),
+        }));
+        assert_eq!(timer.snapshot().await.pomodoro, Some((2, 4)));
     }
 }